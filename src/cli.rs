@@ -0,0 +1,199 @@
+// Command line arguments. Every command takes the options that override the config file, the
+// rest are specific to each command. Running without a command is the same as 'view'.
+
+use clap::Args;
+use clap::Parser;
+use clap::Subcommand;
+
+use config::Config;
+use timecode::Timecode;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "radar_monitor",
+    version,
+    about = "Downloads and animates BOM radar images"
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub overrides: Overrides,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Settings that replace the ones in the config file
+#[derive(Debug, Args)]
+pub struct Overrides {
+    /// Config file to use instead of radar_monitor.toml
+    #[arg(short = 'f', long, global = true, value_name = "FILE")]
+    pub config: Option<String>,
+
+    /// Radar to watch, by BOM id (04, IDR04) or name (newcastle)
+    #[arg(short, long, global = true, value_name = "ID OR NAME")]
+    pub site: Option<String>,
+
+    /// Folder the images are downloaded to
+    #[arg(short, long, global = true, value_name = "FOLDER")]
+    pub dir: Option<String>,
+
+    /// FTP server to download from
+    #[arg(long, global = true, value_name = "HOST:PORT")]
+    pub server: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Download new images and animate them in a window
+    View(ViewArgs),
+    /// Download new images without opening a window, until stopped with SIGTERM or Ctrl-C
+    Download,
+    /// Delete the site's images that are not part of the most recent unbroken run
+    Clean,
+    /// Draw the newest downloaded frame to a PNG file, without a window
+    Render {
+        /// PNG file to write
+        file: String,
+
+        /// Range to draw, by product code (IDR043) or radius (128km). Defaults to the middle one
+        #[arg(short, long, value_name = "CODE OR KM")]
+        range: Option<String>,
+    },
+    /// Write downloaded frames to an animated GIF or PNG, without a window
+    Export(ExportArgs),
+    /// Show how many frames have been downloaded for each radar
+    List,
+    /// List the known radars
+    Sites,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ViewArgs {
+    /// Clean the images folder first, see the clean command
+    #[arg(short, long)]
+    pub clean: bool,
+
+    /// Fill a monitor without a border or cursor, for wall displays
+    #[arg(short, long)]
+    pub kiosk: bool,
+
+    /// Monitor to use in kiosk mode, 0 is the first
+    #[arg(long, value_name = "N")]
+    pub monitor: Option<usize>,
+
+    /// Don't download, just show the frames in the folder and any that another process saves there
+    #[arg(short, long)]
+    pub offline: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// File to write, animated PNG if it ends in .png and GIF if .gif
+    pub file: String,
+
+    /// First frame time in UTC, like the file names (201901312355). Defaults to the loop window
+    #[arg(long, value_name = "TIME")]
+    pub from: Option<Timecode>,
+
+    /// Last frame time in UTC
+    #[arg(long, value_name = "TIME")]
+    pub to: Option<Timecode>,
+
+    /// Time each frame is shown, defaults to the fps speed
+    #[arg(long, value_name = "MS")]
+    pub delay: Option<u64>,
+
+    /// Range to export, by product code (IDR043) or radius (128km). Defaults to the middle one
+    #[arg(short, long, value_name = "CODE OR KM")]
+    pub range: Option<String>,
+}
+
+impl Overrides {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(site) = &self.site {
+            config.site = site.clone();
+        }
+        if let Some(dir) = &self.dir {
+            config.download_dir = dir.clone();
+        }
+        if let Some(server) = &self.server {
+            config.server.host = server.clone();
+        }
+    }
+}
+
+impl ViewArgs {
+    pub fn apply(&self, config: &mut Config) {
+        if self.kiosk {
+            config.kiosk.enabled = true;
+        }
+        if let Some(monitor) = self.monitor {
+            config.kiosk.monitor = monitor;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn options_go_before_or_after_the_command() {
+        let cli = Cli::try_parse_from(["radar_monitor"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from(["radar_monitor", "-s", "02", "view", "--kiosk"]).unwrap();
+        let mut config = Config::default();
+        cli.overrides.apply(&mut config);
+        match cli.command {
+            Some(Command::View(view)) => view.apply(&mut config),
+            other => panic!("Expected view, got {:?}", other),
+        }
+        assert_eq!(config.site, "02");
+        assert!(config.kiosk.enabled);
+
+        let cli = Cli::try_parse_from([
+            "radar_monitor",
+            "export",
+            "loop.gif",
+            "--from",
+            "201901010000",
+            "--dir",
+            "archive/",
+            "--range",
+            "256km",
+        ])
+        .unwrap();
+        assert_eq!(cli.overrides.dir.as_deref(), Some("archive/"));
+        match cli.command {
+            Some(Command::Export(export)) => {
+                assert_eq!(export.file, "loop.gif");
+                assert_eq!(export.from, Some("201901010000".parse().unwrap()));
+                assert_eq!(export.to, None);
+                assert_eq!(export.range.as_deref(), Some("256km"));
+            }
+            other => panic!("Expected export, got {:?}", other),
+        }
+
+        let cli =
+            Cli::try_parse_from(["radar_monitor", "render", "now.png", "-r", "IDR042"]).unwrap();
+        match cli.command {
+            Some(Command::Render { file, range }) => {
+                assert_eq!(file, "now.png");
+                assert_eq!(range.as_deref(), Some("IDR042"));
+            }
+            other => panic!("Expected render, got {:?}", other),
+        }
+
+        assert!(
+            Cli::try_parse_from(["radar_monitor", "export", "x.gif", "--from", "2019"]).is_err()
+        );
+        assert!(Cli::try_parse_from(["radar_monitor", "--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use error::Error;
+use error::Result;
+
+// Config file looked for in the working directory when none is given on the command line
+pub const DEFAULT_CONFIG_FILE: &str = "radar_monitor.toml";
+
+// Runtime configuration. Every field has a default so a config file only needs to contain the
+// values it wants to change.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub download_dir: String, // Folder to keep images in
+    pub site: String,         // Radar site id or name, see radar.rs
+    pub source: String,       // Where to get images from: "ftp", "http" or "local"
+    pub server: ServerConfig,
+    pub http: HttpConfig,
+    pub local: LocalConfig,
+    pub timing: TimingConfig,
+    pub cadence: CadenceConfig,
+    pub retention: RetentionConfig,
+    pub speeds: SpeedConfig,
+    pub loop_window: LoopConfig,
+    pub cache: CacheConfig,
+    pub kiosk: KioskConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub host: String, // host:port of the FTP server
+    pub user: String,
+    pub password: String,
+    pub radar_dir: String,          // Directory holding the radar images
+    pub transparencies_dir: String, // Directory holding the background and location layers
+}
+
+// Urls used by the "http" source
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub radar_url: String, // Must serve a directory listing of the radar images, no default
+    pub transparencies_url: String,
+    pub timeout_secs: u64,
+}
+
+// Folders used by the "local" source
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LocalConfig {
+    pub radar_dir: String,
+    pub transparencies_dir: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TimingConfig {
+    pub wait_mins: usize,      // Time to wait after a successful download
+    pub retry_mins: usize,     // Time to wait before checking again when nothing new was found
+    pub background_hours: u64, // Time between refreshes of the background and location layers
+    pub watch_secs: u64,       // Time between checks of the folder when viewing without downloading
+}
+
+// Expected time between radar frames, used when cleaning to decide which frames are contiguous
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CadenceConfig {
+    pub interval_mins: i64, // 0 to detect the interval from the gaps between images on disk
+    pub tolerance_mins: i64, // How far off the interval a gap can be and still count
+    pub sites: HashMap<String, i64>, // Per site interval_mins, keyed by site id
+}
+
+// Limits on how many downloaded frames are kept for each range. 0 means no limit, and nothing is
+// deleted unless a limit is set
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub max_age_hours: i64,
+    pub max_frames: usize,
+    pub max_megabytes: u64,
+}
+
+// Playback speed in frames per second, changed by step_fps at a time within min_fps -> max_fps
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SpeedConfig {
+    pub fps: f32,
+    pub step_fps: f32,
+    pub min_fps: f32,
+    pub max_fps: f32,
+    pub hold_latest_ms: u64, // Extra time to show the newest frame before looping
+    pub crossfade: f32,      // Fraction of each frame spent fading into the next, 0 for hard cuts
+}
+
+// Which of the frames on disk the viewer animates: the newest 'hours' of frames (0 for all), but
+// no more than 'max_frames' (0 for no limit). 'hour_steps' are the lengths the keys step through
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LoopConfig {
+    pub hours: f32,
+    pub max_frames: usize,
+    pub hour_steps: Vec<f32>,
+}
+
+// Viewer texture memory. Textures over the budget are evicted least recently used first and read
+// from disk again when needed. Images are decoded by 'decode_threads' workers, starting
+// 'predecode_frames' frames ahead of the one on screen
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub max_megabytes: usize, // 0 for no limit
+    pub predecode_frames: usize,
+    pub decode_threads: usize,
+}
+
+// Wall display mode: borderless fullscreen on 'monitor' (0 is the first) with the cursor hidden.
+// The quit keys need Ctrl held, and the downloader is restarted if it fails
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct KioskConfig {
+    pub enabled: bool,
+    pub monitor: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            download_dir: "img/".to_string(),
+            site: super::radar::DEFAULT_SITE.to_string(),
+            source: "ftp".to_string(),
+            server: ServerConfig::default(),
+            http: HttpConfig::default(),
+            local: LocalConfig::default(),
+            timing: TimingConfig::default(),
+            cadence: CadenceConfig::default(),
+            retention: RetentionConfig::default(),
+            speeds: SpeedConfig::default(),
+            loop_window: LoopConfig::default(),
+            cache: CacheConfig::default(),
+            kiosk: KioskConfig::default(),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "ftp2.bom.gov.au:21".to_string(),
+            user: "anonymous".to_string(),
+            password: "guest".to_string(),
+            radar_dir: "anon/gen/radar".to_string(),
+            transparencies_dir: "anon/gen/radar_transparencies".to_string(),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            radar_url: String::new(),
+            transparencies_url: "http://www.bom.gov.au/products/radar_transparencies/".to_string(),
+            timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for LocalConfig {
+    fn default() -> Self {
+        LocalConfig {
+            radar_dir: "radar/".to_string(),
+            transparencies_dir: "radar_transparencies/".to_string(),
+        }
+    }
+}
+
+impl Default for TimingConfig {
+    fn default() -> Self {
+        TimingConfig {
+            wait_mins: 4,
+            retry_mins: 1,
+            background_hours: 24,
+            watch_secs: 5,
+        }
+    }
+}
+
+impl Default for CadenceConfig {
+    fn default() -> Self {
+        CadenceConfig {
+            interval_mins: 0,
+            tolerance_mins: 1,
+            sites: HashMap::new(),
+        }
+    }
+}
+
+impl Default for SpeedConfig {
+    fn default() -> Self {
+        SpeedConfig {
+            fps: 10.0,
+            step_fps: 1.0,
+            min_fps: 1.0,
+            max_fps: 30.0,
+            hold_latest_ms: 1000,
+            crossfade: 0.5,
+        }
+    }
+}
+
+impl Default for LoopConfig {
+    fn default() -> Self {
+        LoopConfig {
+            hours: 3.0,
+            max_frames: 0,
+            hour_steps: vec![1.0, 3.0, 6.0, 12.0, 24.0],
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            max_megabytes: 256,
+            predecode_frames: 4,
+            decode_threads: 2,
+        }
+    }
+}
+
+impl Config {
+    // Load the config from 'path', or from DEFAULT_CONFIG_FILE if no path is given.
+    // A missing default file is not an error, the built in defaults are used instead.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let contents = match path {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("Config file '{}': {}", path, e)))?,
+            None => match fs::read_to_string(DEFAULT_CONFIG_FILE) {
+                Ok(contents) => contents,
+                Err(_) => return Ok(Config::default()),
+            },
+        };
+
+        Config::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|e| Error::Parse(format!("Config file: {}", e)))
+    }
+
+    // Folder holding the images for product code 'lc_code', with a trailing slash
+    pub fn image_dir(&self, lc_code: &str) -> String {
+        format!("{}/{}/", self.download_dir.trim_end_matches('/'), lc_code)
+    }
+
+    // Path of background or location layer 'name', e.g. IDR043.background.png. The layers are
+    // kept with the images so a copy of the download folder can be viewed on its own
+    pub fn layer_path(&self, name: &str) -> String {
+        format!("{}/{}", self.download_dir.trim_end_matches('/'), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli::Overrides;
+
+    #[test]
+    fn missing_values_keep_their_defaults() {
+        let config = Config::parse(
+            "site = \"Sydney\"\n\
+             [speeds]\n\
+             fps = 8.0\n",
+        )
+        .unwrap();
+        let defaults = Config::default();
+
+        assert_eq!(config.site, "Sydney");
+        assert_eq!(config.speeds.fps, 8.0);
+        assert_eq!(config.speeds.max_fps, defaults.speeds.max_fps);
+        assert_eq!(config.download_dir, defaults.download_dir);
+        assert_eq!(config.server.host, defaults.server.host);
+        assert_eq!(config.timing.wait_mins, defaults.timing.wait_mins);
+    }
+
+    #[test]
+    fn invalid_values_are_parse_errors() {
+        for contents in &[
+            "[speeds]\nfps = \"fast\"\n",
+            "site = \n",
+            "[kiosk]\nmonitor = -1\n",
+        ] {
+            assert!(matches!(Config::parse(contents), Err(Error::Parse(_))));
+        }
+    }
+
+    #[test]
+    fn command_line_overrides_the_file() {
+        let mut config = Config::parse(
+            "site = \"Sydney\"\n\
+             download_dir = \"archive/\"\n\
+             [server]\n\
+             host = \"mirror:21\"\n",
+        )
+        .unwrap();
+        let overrides = Overrides {
+            config: None,
+            site: Some("02".to_string()),
+            dir: None,
+            server: Some("localhost:2121".to_string()),
+        };
+        overrides.apply(&mut config);
+
+        assert_eq!(config.site, "02");
+        assert_eq!(config.download_dir, "archive/");
+        assert_eq!(config.server.host, "localhost:2121");
+    }
+}
@@ -0,0 +1,276 @@
+// Running the downloader on its own as a collector, e.g. as a service on a machine without a
+// display. Everything it does is logged a line at a time with the time, and it stops cleanly
+// between downloads on SIGTERM or Ctrl-C. A lock file stops two processes downloading into the
+// same folder, whether collectors or viewers.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Local;
+
+use config::Config;
+use downloader;
+use error::Error;
+use error::Result;
+use event::DownloadEvent;
+use radar::RadarSite;
+use source;
+
+// Name of the lock file in the download folder, holding the collector's process id
+pub const LOCK_FILE_NAME: &str = "radar_monitor.lock";
+
+// How often to check for a shutdown request while no events are arriving
+const SHUTDOWN_POLL: Duration = Duration::from_millis(250);
+
+// How many times, and how far apart, to read a lock file that doesn't hold a process id yet
+const OWNER_READ_ATTEMPTS: usize = 5;
+const OWNER_READ_WAIT: Duration = Duration::from_millis(100);
+
+// Download new frames for 'site' until asked to stop
+pub fn run(site: &RadarSite, config: &Config) -> Result<()> {
+    let _lock = LockFile::acquire(&config.download_dir)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst))
+        .map_err(|e| io::Error::other(format!("Couldn't handle signals: {}", e)))?;
+
+    log(&format!(
+        "Collecting {} ({}) into {}",
+        site.name,
+        site.codes().join(", "),
+        config.download_dir
+    ));
+    // A collector has to survive the network or the source being down when it starts, so a
+    // failed start is retried by the supervisor like any other failure
+    let mut source = source::from_config(config)?;
+    let started = match downloader::init(source.as_mut(), site, config, &report) {
+        Ok(index) => Some((source, index)),
+        Err(e) => {
+            log(&format!("Couldn't start, retrying: {}", e));
+            None
+        }
+    };
+
+    let (sender, receiver) = channel();
+    let dl_site = site.clone();
+    let dl_config = config.clone();
+    let downloader = thread::spawn(move || {
+        downloader::run_supervised(sender, started, &dl_site, &dl_config, true)
+    });
+
+    while !stop.load(Ordering::SeqCst) {
+        match receiver.recv_timeout(SHUTDOWN_POLL) {
+            Ok(event) => report(event),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    // The downloader stops the next time it tries to report something, which it does at least
+    // every second while waiting. A download in progress is finished first
+    log("Stopping");
+    drop(receiver);
+    let result = downloader
+        .join()
+        .unwrap_or_else(|_| Err(Error::Crashed("Downloader panicked".to_string())));
+    log("Stopped");
+    result
+}
+
+// Print 'message' as a line of the log
+fn log(message: &str) {
+    println!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}
+
+// Log 'event' if it's more than a progress update
+fn report(event: DownloadEvent) {
+    if let Some(message) = describe(&event) {
+        log(&message);
+    }
+}
+
+// Log line for 'event', or None for the ones that are only progress updates
+fn describe(event: &DownloadEvent) -> Option<String> {
+    match event {
+        DownloadEvent::Waiting(_) | DownloadEvent::NewFrame(_) => None,
+        DownloadEvent::Checking => Some("Checking for new frames".to_string()),
+        DownloadEvent::Watching => Some("Watching for new frames".to_string()),
+        DownloadEvent::DownloadStarted(name) => Some(format!("Downloading {}", name)),
+        DownloadEvent::DownloadFinished(name) => Some(format!("Downloaded {}", name)),
+        DownloadEvent::Pruned(paths) => Some(format!("Pruned {} old frames", paths.len())),
+        DownloadEvent::BackgroundRefreshed(code) => {
+            Some(format!("Refreshed background layers for {}", code))
+        }
+        DownloadEvent::Error(e) => Some(format!("Error: {}", e)),
+        DownloadEvent::ServerUnreachable(e) => Some(format!("Server unreachable: {}", e)),
+    }
+}
+
+// Lock on a download folder, released when dropped. A lock left behind by a collector that
+// didn't shut down cleanly is taken over, as long as its process has gone
+pub struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    pub fn acquire(dir: &str) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(LOCK_FILE_NAME);
+
+        // Twice, in case the first attempt finds a stale lock
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    write!(file, "{}", process::id())?;
+                    return Ok(LockFile { path });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    match lock_owner(&path) {
+                        Some(pid) if process_running(pid) => {
+                            return Err(Error::Locked(format!(
+                                "{} is being downloaded to by process {}",
+                                dir, pid
+                            )))
+                        }
+                        Some(_) => {
+                            log(&format!("Removing stale lock file {}", path.display()));
+                            fs::remove_file(&path)?;
+                        }
+                        // Released while it was being read, so try again
+                        None if !path.exists() => (),
+                        // Without a process id it can't be known to be stale
+                        None => {
+                            return Err(Error::Locked(format!(
+                                "{} is being downloaded to by an unknown process",
+                                dir
+                            )))
+                        }
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(Error::Locked(format!("Couldn't create {}", path.display())))
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// The process id in the lock file at 'path'. Its owner creates it empty and then writes its id,
+// so one that can't be read or parsed yet is given a moment before giving up on it
+fn lock_owner(path: &Path) -> Option<u32> {
+    for attempt in 0..OWNER_READ_ATTEMPTS {
+        if attempt > 0 {
+            thread::sleep(OWNER_READ_WAIT);
+        }
+        let owner = fs::read_to_string(path)
+            .ok()
+            .and_then(|pid| pid.trim().parse().ok());
+        if owner.is_some() {
+            return owner;
+        }
+    }
+
+    None
+}
+
+// True if process 'pid' is still running. Only Linux can tell, elsewhere every lock is assumed
+// to be live and has to be removed by hand
+#[cfg(target_os = "linux")]
+fn process_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use temp_dir::TempDir;
+
+    #[test]
+    fn lock_is_exclusive_until_dropped() {
+        let temp = TempDir::new("lock");
+        let dir = temp.path();
+        let path = Path::new(dir).join(LOCK_FILE_NAME);
+
+        let lock = LockFile::acquire(dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            process::id().to_string()
+        );
+        assert!(matches!(LockFile::acquire(dir), Err(Error::Locked(_))));
+
+        drop(lock);
+        assert!(!path.exists());
+        let _lock = LockFile::acquire(dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn stale_locks_are_taken_over() {
+        let temp = TempDir::new("stale");
+        let path = Path::new(temp.path()).join(LOCK_FILE_NAME);
+
+        // Larger than any real process id
+        fs::write(&path, "4294967295").unwrap();
+        let _lock = LockFile::acquire(temp.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            process::id().to_string()
+        );
+    }
+
+    #[test]
+    fn unreadable_locks_are_not_taken_over() {
+        let temp = TempDir::new("unreadable");
+        let path = Path::new(temp.path()).join(LOCK_FILE_NAME);
+
+        // As left between another process creating it and writing its id
+        fs::write(&path, "").unwrap();
+        assert!(matches!(
+            LockFile::acquire(temp.path()),
+            Err(Error::Locked(_))
+        ));
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn progress_events_are_not_logged() {
+        assert_eq!(describe(&DownloadEvent::Waiting(0.5)), None);
+        assert_eq!(
+            describe(&DownloadEvent::DownloadFinished(
+                "IDR043.T.201901010000.png".to_string()
+            )),
+            Some("Downloaded IDR043.T.201901010000.png".to_string())
+        );
+        assert_eq!(
+            describe(&DownloadEvent::Pruned(vec![
+                "a".to_string(),
+                "b".to_string()
+            ])),
+            Some("Pruned 2 old frames".to_string())
+        );
+    }
+}
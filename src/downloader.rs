@@ -1,60 +1,224 @@
-extern crate ftp;
-
-use ftp::FtpError;
-use ftp::FtpStream;
-use std;
 use std::fs;
-use std::fs::File;
+use std::io;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 use std::str;
-use std::str::FromStr;
 use std::sync::mpsc::Sender;
+use std::thread;
 use std::thread::sleep;
 use std::time::Duration;
+use std::time::Instant;
 
-use super::CODE_HIGH;
-use super::CODE_LOW;
-use super::CODE_MID;
-use super::DL_DIR;
-
-//Simple timecode struct used to determine file contiguousness
-#[derive(Debug)]
-struct Timecode {
-    year: usize,
-    month: usize,
-    day: usize,
-    hour: usize,
-    min: usize,
+use ftp::FtpError;
+
+use config::Config;
+use error::decode_error;
+use error::Error;
+use error::Result;
+use event::DownloadEvent;
+use frame_index::remove_legacy_prefixes;
+use frame_index::Frame;
+use frame_index::FrameIndex;
+use radar::RadarSite;
+use retention;
+use source;
+use source::RadarSource;
+use timecode::Timecode;
+use verify::quarantine_corrupt;
+use verify::verify_png;
+use verify::write_atomic;
+
+// Run 'run_loop' on its own thread, starting from the source and index 'init' returned. If
+// 'restart' is set and it fails or panics it's started again after the retry wait, with a fresh
+// connection to the source that's initialised again. With 'restart' set 'started' can be None if
+// 'init' failed, and it's retried the same way. Returns once the viewer has gone away, or the loop
+// fails without 'restart'
+pub fn run_supervised(
+    sender: Sender<DownloadEvent>,
+    started: Option<(Box<dyn RadarSource + Send>, FrameIndex)>,
+    site: &RadarSite,
+    config: &Config,
+    restart: bool,
+) -> Result<()> {
+    // The viewer can also go away while it's waiting to restart, which is a clean stop too
+    match supervise(&sender, started, site, config, restart) {
+        Err(Error::Disconnected) => Ok(()),
+        result => result,
+    }
 }
 
-pub fn run_loop(sender: Sender<f32>) -> Result<(), ()> {
+fn supervise(
+    sender: &Sender<DownloadEvent>,
+    started: Option<(Box<dyn RadarSource + Send>, FrameIndex)>,
+    site: &RadarSite,
+    config: &Config,
+    restart: bool,
+) -> Result<()> {
+    let mut parts = started;
+
     loop {
-        // Wait for 4 minutes, then check the server every minute until we get at least
+        if let Some((mut source, index)) = parts.take() {
+            let (loop_sender, loop_site, loop_config) =
+                (sender.clone(), site.clone(), config.clone());
+            let handle = thread::spawn(move || {
+                run_loop(
+                    loop_sender,
+                    source.as_mut(),
+                    index,
+                    &loop_site,
+                    &loop_config,
+                )
+            });
+
+            let error = match handle.join() {
+                Ok(Ok(())) | Ok(Err(Error::Disconnected)) => return Ok(()),
+                Ok(Err(e)) => e,
+                Err(panic) => Error::Crashed(panic_message(panic)),
+            };
+            if !restart {
+                return Err(error);
+            }
+            send_error(sender, error)?;
+        }
+
+        // The viewer is already running, so it's sent everything 'init' does
+        wait_mins(config.timing.retry_mins, sender)?;
+        let notify = |event| {
+            let _ = sender.send(event);
+        };
+        let restarted = source::from_config(config).and_then(|mut source| {
+            let index = init(source.as_mut(), site, config, &notify)?;
+            Ok((source, index))
+        });
+        match restarted {
+            Ok(restarted) => parts = Some(restarted),
+            Err(e) => send_error(sender, e)?,
+        }
+    }
+}
+
+// The message a thread panicked with, if it was a string
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+// Download new files forever, reporting everything it does to the viewer as DownloadEvents.
+// Errors from the source or file system are treated as transient, and are retried after a short
+// wait. Old frames are pruned after each download, and the background layers are refreshed
+// every so often. Only returns once the viewer has gone away.
+pub fn run_loop(
+    sender: Sender<DownloadEvent>,
+    source: &mut dyn RadarSource,
+    mut index: FrameIndex,
+    site: &RadarSite,
+    config: &Config,
+) -> Result<()> {
+    let notify = |event| {
+        // A closed channel is noticed by the next send() so it's safe to ignore here
+        let _ = sender.send(event);
+    };
+    let mut last_background = Instant::now();
+
+    loop {
+        // Wait for a few minutes, then check the server regularly until we get at least
         // 1 new file
-        wait_mins(4, &sender);
+        wait_mins(config.timing.wait_mins, &sender)?;
+
+        loop {
+            send(&sender, DownloadEvent::Checking)?;
+            match save_files(source, &mut index, site, config, &notify) {
+                Ok(ref frames) if frames.is_empty() => (),
+                Ok(_) => break,
+                Err(e) => send_error(&sender, e)?,
+            }
+            wait_mins(config.timing.retry_mins, &sender)?;
+        }
+
+        let background_age = Duration::from_secs(config.timing.background_hours * 60 * 60);
+        if last_background.elapsed() >= background_age {
+            for lc_code in site.codes() {
+                match init_background(source, lc_code, config) {
+                    Ok(()) => send(
+                        &sender,
+                        DownloadEvent::BackgroundRefreshed(lc_code.to_string()),
+                    )?,
+                    Err(e) => send_error(&sender, e)?,
+                }
+            }
+            last_background = Instant::now();
+        }
 
-        while save_files().is_err() {
-            wait_mins(1, &sender)
+        match retention::prune(site, config) {
+            Ok(ref removed) if removed.is_empty() => (),
+            Ok(removed) => {
+                index.remove_paths(&removed);
+                send(&sender, DownloadEvent::Pruned(removed))?;
+            }
+            Err(e) => send_error(&sender, e)?,
         }
     }
 }
 
-// Connect to the BOM ftp server and download any new files
-// Saves files for all three zoom levels
-// Files are saved to the folder DL_DIR/lc_code and are prefixed with an 'x' to designate them as
-// new.
-// Returns Ok(()) if everything was ok. Propogates an error if there is an ftp error
-pub fn save_files() -> ftp::types::Result<()> {
-    // Connect to the server, login, change directory
-    let mut ftp_stream = FtpStream::connect("ftp2.bom.gov.au:21")?;
-    ftp_stream.login("anonymous", "guest")?;
-    ftp_stream.cwd("anon/gen/radar")?;
+// Send an event to the viewer. Fails if the viewer has gone away
+fn send(sender: &Sender<DownloadEvent>, event: DownloadEvent) -> Result<()> {
+    sender.send(event).map_err(|_| Error::Disconnected)
+}
+
+fn send_error(sender: &Sender<DownloadEvent>, e: Error) -> Result<()> {
+    if is_unreachable(&e) {
+        send(sender, DownloadEvent::ServerUnreachable(e))
+    } else {
+        send(sender, DownloadEvent::Error(e))
+    }
+}
 
+// True if 'e' means the source couldn't be contacted at all
+fn is_unreachable(e: &Error) -> bool {
+    match e {
+        Error::Ftp(FtpError::ConnectionError(_)) => true,
+        Error::Http(e) => matches!(**e, ureq::Error::Transport(_)),
+        _ => false,
+    }
+}
+
+// Download any new files from the source
+// Saves files for every range (zoom level) of the site, skipping any already in the index
+// Files are saved to the folder download_dir/lc_code under their BOM names, and added to the index
+// once they're complete. Files that aren't valid images are skipped, and tried again next time.
+// Progress and each new frame are passed to 'notify' as they happen.
+// Returns the new frames. Propogates any source or file system errors
+pub fn save_files(
+    source: &mut dyn RadarSource,
+    index: &mut FrameIndex,
+    site: &RadarSite,
+    config: &Config,
+    notify: &dyn Fn(DownloadEvent),
+) -> Result<Vec<Frame>> {
+    let result = download_new_files(source, index, site, config, notify);
+
+    // Disconnect from the server
+    source.finish();
+    result
+}
+
+fn download_new_files(
+    source: &mut dyn RadarSource,
+    index: &mut FrameIndex,
+    site: &RadarSite,
+    config: &Config,
+    notify: &dyn Fn(DownloadEvent),
+) -> Result<Vec<Frame>> {
     // Find out which files are currently on the server
-    let filenames = ftp_stream.nlst(Option::None)?;
-    let mut downloaded_anything = false;
+    let filenames = source.list_frames()?;
+    let mut new_frames = Vec::new();
 
-    for lc_code in &[CODE_LOW, CODE_MID, CODE_HIGH] {
+    for lc_code in site.codes() {
         let mut downloads = 0;
         let mut filenames = filenames.clone();
 
@@ -62,204 +226,204 @@ pub fn save_files() -> ftp::types::Result<()> {
         filenames.retain(|e| e.contains(lc_code) && !e.contains(".gif"));
 
         for file_name in filenames {
-            // Prefix filename to designate it as a new file
-            let file_name_x = "x".to_string() + &file_name;
-
-            // Check if the file already exists locally.
-            // Open will return an error if it does not exist, so err = good.
-            // If open succeeds, the file exists and we move to the next file
-            if File::open(DL_DIR.to_string() + lc_code + "/" + &file_name).is_ok() {
+            // Skip anything that isn't a radar image, or that we already have
+            let time = match Timecode::from_file_name(&file_name) {
+                Ok(time) => time,
+                Err(_) => continue,
+            };
+            if index.contains(lc_code, time) {
                 continue;
             }
 
-            downloaded_anything = true;
-
             // Print a message (one line only regardless of number of files)
-            print!("\r({:02}) downloading '{}...'", downloads + 1, file_name);
-            std::io::stdout().flush().expect("Error flushing stdout");
+            if interactive() {
+                print!("\r({:02}) downloading '{}...'", downloads + 1, file_name);
+                io::stdout().flush()?;
+            }
+            notify(DownloadEvent::DownloadStarted(file_name.clone()));
 
             // Get the file from the server
-            let remote_file = ftp_stream.simple_retr(&file_name)?;
-
-            // Create a new file locally
-            let mut file = File::create(DL_DIR.to_string() + lc_code + "/" + &file_name_x)
-                .expect("Error creating file on disk");
-
-            // Write the file
-            file.write_all(remote_file.into_inner().as_slice())
-                .expect("Error writing file to disk");
+            let remote_file = source.fetch_frame(&file_name)?;
 
+            // Check it's a whole image, then write it to disk
+            if let Err(e) = verify_png(&file_name, &remote_file) {
+                notify(DownloadEvent::Error(e));
+                continue;
+            }
+            let path = config.image_dir(lc_code) + &file_name;
+            write_atomic(&path, &remote_file)?;
+
+            let frame = Frame {
+                lc_code: lc_code.to_string(),
+                time,
+                path,
+            };
+            index.insert(frame.clone());
+            notify(DownloadEvent::DownloadFinished(file_name));
+            notify(DownloadEvent::NewFrame(frame.clone()));
+            new_frames.push(frame);
             downloads += 1;
         }
 
-        if downloads > 0 {
+        if downloads > 0 && interactive() {
             println!();
         }
     }
 
-    // Disconnect from the server
-    let _ = ftp_stream.quit();
-    if downloaded_anything {
-        Ok(())
-    } else {
-        Err(FtpError::InvalidResponse("No new items :)".to_string()))
-    }
+    Ok(new_frames)
 }
 
 // Wait for 'mins' minutes while printing a report of how long remains.
-pub fn wait_mins(mins: usize, sender: &Sender<f32>) {
+// Fails if the viewer is no longer listening.
+pub fn wait_mins(mins: usize, sender: &Sender<DownloadEvent>) -> Result<()> {
     let max_secs = mins * 60;
     let mut secs = max_secs;
 
     let one_sec = Duration::new(1, 0);
 
     while secs > 0 {
-        print!("\rWaiting {} seconds...     ", secs);
-        std::io::stdout().flush().expect("Error flushing stdout");
+        if interactive() {
+            print!("\rWaiting {} seconds...     ", secs);
+            io::stdout().flush()?;
+        }
 
         // How far through the wait time from 0.0 -> 1.0
         let wait_scale = 1.0 - (secs as f32 / max_secs as f32);
-        sender.send(wait_scale).expect("Sending fail");
+        send(sender, DownloadEvent::Waiting(wait_scale))?;
 
         sleep(one_sec);
 
         secs -= 1;
     }
 
-    if mins == 1 {
-        println!("\rWaited 1 minute.       ");
-    } else {
-        println!("\rWaited {} minutes.      ", mins);
+    if interactive() {
+        if mins == 1 {
+            println!("\rWaited 1 minute.       ");
+        } else {
+            println!("\rWaited {} minutes.      ", mins);
+        }
     }
+    Ok(())
+}
+
+// True if the output is going to a terminal rather than a log file, so there's someone to show
+// the progress lines to
+fn interactive() -> bool {
+    io::stdout().is_terminal()
 }
 
 // Run first time initialisation tasks such as creating directories and priming with images
-// Will panic if initialisation fails
-pub fn init() {
+// Downloads, frames that are pruned or quarantined and any other problems that aren't fatal are
+// passed to 'notify'.
+// Returns the index of the frames on disk for the download thread to keep up to date
+pub fn init(
+    source: &mut dyn RadarSource,
+    site: &RadarSite,
+    config: &Config,
+    notify: &dyn Fn(DownloadEvent),
+) -> Result<FrameIndex> {
     // Attempt to create the download directory, not caring if it succeeds or if it fails
     // (the directory already exists)
-    match fs::create_dir(DL_DIR) {
+    match fs::create_dir(&config.download_dir) {
         Ok(_) | Err(_) => (),
     };
 
-    init_background(CODE_LOW).expect("Initialisation Failure");
-    init_background(CODE_MID).expect("Initialisation Failure");
-    init_background(CODE_HIGH).expect("Initialisation Failure");
+    for lc_code in site.codes() {
+        init_background(source, lc_code, config)?;
+        notify(DownloadEvent::BackgroundRefreshed(lc_code.to_string()));
+    }
+
+    // Index what's already on disk so it isn't downloaded again. Not being able to download
+    // anything yet isn't fatal
+    remove_legacy_prefixes(site, config)?;
+    let mut index = FrameIndex::scan(site, config)?;
+    for path in quarantine_corrupt(&mut index, &site.codes(), config)? {
+        let error = decode_error(&path, "Corrupt, moved to quarantine");
+        notify(DownloadEvent::Error(error));
+    }
+    if let Err(e) = save_files(source, &mut index, site, config, notify) {
+        notify(DownloadEvent::Error(e));
+    }
 
-    // Any pre-existing files will not be prefixed, and will not be re-downloaded by
-    // save_files(), once that has completed we re-prefix the pre-existing files to be made
-    // into textures by the other thread
-    save_files().ok();
+    // Prune before the viewer loads everything on disk
+    let removed = retention::prune(site, config)?;
+    if !removed.is_empty() {
+        index.remove_paths(&removed);
+        notify(DownloadEvent::Pruned(removed));
+    }
 
-    mark_files_as_new(CODE_LOW);
-    mark_files_as_new(CODE_MID);
-    mark_files_as_new(CODE_HIGH);
+    Ok(index)
 }
 
 // Save the radar background for location code 'lc_code' and create the subdirectory for
 // that radar's images
-// Propogates any FTP errors upstream, panics on file system errors
-pub fn init_background(lc_code: &str) -> ftp::types::Result<()> {
+// Propogates any source or file system errors upstream
+pub fn init_background(source: &mut dyn RadarSource, lc_code: &str, config: &Config) -> Result<()> {
     // Do nothing on an error. Generally an error here means that the directory
     // already exists which is what we want
-    match fs::create_dir(DL_DIR.to_string() + lc_code + "/") {
+    match fs::create_dir(config.image_dir(lc_code)) {
         Ok(_) | Err(_) => (),
     };
 
     let background_file_name = &(lc_code.to_string() + ".background.png");
     let location_file_name = &(lc_code.to_string() + ".locations.png");
 
-    // Connect to the server, login, navigate to directory
-    let mut ftp_stream = FtpStream::connect("ftp2.bom.gov.au:21")?;
-    ftp_stream.login("anonymous", "guest")?;
-    ftp_stream.cwd("anon/gen/radar_transparencies")?;
-
     // Get the files from the server
-    let background_file = ftp_stream.simple_retr(background_file_name)?;
-    let location_file = ftp_stream.simple_retr(location_file_name)?;
-
-    // Create a new file locally (overwriting if already exists)
-    let mut bg_file = File::create(background_file_name).expect("Error creating file on disk");
-    let mut lc_file = File::create(location_file_name).expect("Error creating file on disk");
-
-    // Write the files
-    bg_file
-        .write_all(background_file.into_inner().as_slice())
-        .expect("Error writing file to disk");
-    lc_file
-        .write_all(location_file.into_inner().as_slice())
-        .expect("Error writing file to disk");
-    // Disconnect from the server
-    let _ = ftp_stream.quit();
+    let background_file = source.fetch_transparency(background_file_name);
+    let location_file = source.fetch_transparency(location_file_name);
+    source.finish();
+    let background_file = background_file?;
+    let location_file = location_file?;
+
+    // Create new files locally (overwriting if already exists)
+    verify_png(background_file_name, &background_file)?;
+    verify_png(location_file_name, &location_file)?;
+    write_atomic(&config.layer_path(background_file_name), &background_file)?;
+    write_atomic(&config.layer_path(location_file_name), &location_file)?;
 
     Ok(())
 }
 
-// For all files in the folder DL_DIR/location_code/ which do not have an 'x' prefix, add that
-// prefix to their filename.
-fn mark_files_as_new(location_code: &str) {
-    // Read the directory and convert to filenames
-    let dir = DL_DIR.to_string() + location_code + "/";
-    let files: Vec<_> = fs::read_dir(&dir)
-        .expect("Error reading directory")
-        .map(|e| {
-            e.expect("Error reading image filename")
-                .file_name()
-                .into_string()
-                .expect("Error extracting image filename")
-        })
-        .collect();
-
-    // Filter out any files which already have the prefix
-    let mut file_names = files
-        .iter()
-        .filter(|e| !e.starts_with('x'))
-        .collect::<Vec<_>>();
-
-    file_names.sort();
-
-    // Prefix each file
-    for file_name in file_names {
-        let new_name = "x".to_string() + file_name;
-        fs::rename(
-            &(dir.to_string() + file_name),
-            &(dir.to_string() + &new_name),
-        )
-        .expect("Error renaming file");
+// Names of all the files in 'dir'. Names that aren't valid unicode can't be BOM images, so
+// they're skipped
+pub fn file_names_in(dir: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        if let Ok(name) = entry?.file_name().into_string() {
+            names.push(name);
+        }
     }
+    Ok(names)
 }
 
-// Removes non-contiguous files from each image directory
-// i.e. leaves only the most recent streak of contiguous images based on the
-// assumption that each image from the radar comes five minutes after the previous one
-pub fn clean() {
-    let file_error = "Error reading file system";
+// Removes non-contiguous files from each image directory of 'site'
+// i.e. leaves only the most recent streak of contiguous images, where each image is expected to come
+// one radar interval after the previous one (see expected_interval)
+// Anything that isn't a radar image, such as a partial download, is left alone
+pub fn clean(site: &RadarSite, config: &Config) -> Result<()> {
+    let index = FrameIndex::scan(site, config)?;
 
-    // If DL_DIR doesn't exist, there is nothing to clean
-    let dirs = match fs::read_dir(DL_DIR) {
-        Ok(d) => d,
-        Err(_) => return,
-    };
+    // Iterate through the ranges (zoom levels)
+    for lc_code in site.codes() {
+        let frames = index.frames(lc_code);
+        let times: Vec<_> = frames.iter().map(|frame| frame.time).collect();
 
-    // Iterate through the subdirectories (zoom levels)
-    for dir in dirs {
-        let files = fs::read_dir(dir.expect(file_error).path()).expect(file_error);
-
-        // Get the path for each file
-        let mut file_names: Vec<_> = files.map(|e| e.expect(file_error).path()).collect();
-        file_names.sort();
+        let interval = match expected_interval(lc_code, &times, config) {
+            Some(interval) => interval,
+            None => continue,
+        };
+        let tolerance = chrono::Duration::minutes(config.cadence.tolerance_mins);
 
         let mut del = 0;
         // Iterate through the files in reverse order (newest to oldest)
         // If del is true we have already found a break in continuity and are just deleting
-        for (i, prev) in file_names.iter().enumerate().rev() {
-            if i + 1 != file_names.len() {
+        for (i, prev) in frames.iter().enumerate().rev() {
+            if i + 1 != frames.len() {
                 // Look ahead 1
-                let file = &file_names[i + 1];
-                if del > 0 || !consecutive_files(prev, file) {
+                if del > 0 || !consecutive(times[i], times[i + 1], interval, tolerance) {
                     del += 1;
-                    print!("\r({:02}) Deleting: {:?}", del, prev);
-                    fs::remove_file(prev).expect(file_error);
+                    print!("\r({:02}) Deleting: {}", del, prev.path);
+                    fs::remove_file(&prev.path)?;
                 }
             }
         }
@@ -267,55 +431,497 @@ pub fn clean() {
             println!();
         }
     }
+    Ok(())
 }
 
-// Converts a file path to a Timecode object
-// Assumes that the file given is a BOM radar image
-fn timecode_from_path(name: &std::path::Path) -> Timecode {
-    // Get the file name
-    let name = name
-        .file_name()
-        .expect("Error reading file name")
-        .to_str()
-        .expect("String conversion error");
-
-    // Split at each dot and take the third (just the timecode)
-    let string = name.split('.').nth(2).expect("Unexpected file format");
-
-    // Split the timecode into the respective parts
-    let (year, string) = string.split_at(4);
-    let (month, string) = string.split_at(2);
-    let (day, string) = string.split_at(2);
-    let (hour, string) = string.split_at(2);
-    let (min, _) = string.split_at(2);
-
-    // Convert into numbers and return
-    Timecode {
-        year: usize::from_str(year).expect("Unexpected file format"),
-        month: usize::from_str(month).expect("Unexpected file format"),
-        day: usize::from_str(day).expect("Unexpected file format"),
-        hour: usize::from_str(hour).expect("Unexpected file format"),
-        min: usize::from_str(min).expect("Unexpected file format"),
+// The time expected between frames of product 'lc_code'. Uses the configured interval for the
+// site if there is one, otherwise the median gap between 'times' (which must be sorted).
+// Returns None if there aren't enough frames to tell.
+fn expected_interval(
+    lc_code: &str,
+    times: &[Timecode],
+    config: &Config,
+) -> Option<chrono::Duration> {
+    let cadence = &config.cadence;
+    let site_id = lc_code.get(3..5).unwrap_or_default();
+    let mins = cadence
+        .sites
+        .get(site_id)
+        .cloned()
+        .unwrap_or(cadence.interval_mins);
+
+    if mins > 0 {
+        return Some(chrono::Duration::minutes(mins));
     }
+
+    let mut gaps: Vec<_> = times
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|gap| *gap > chrono::Duration::zero())
+        .collect();
+    gaps.sort();
+    gaps.get(gaps.len() / 2).cloned()
 }
 
-// Returns true if the two files given are consecutive, in that the timecodes contained
-// in the file names are 5 minutes apart
-fn consecutive_files(prev: &std::path::Path, next: &std::path::Path) -> bool {
-    // Convert filenames to timecodes
-    let prev = timecode_from_path(prev);
-    let next = timecode_from_path(next);
-
-    // Chain through the different levels of time timecode
-    // Not entirely sure that every level works but it at least works across
-    // Hour boundaries so for the most part is ok
-    if prev.min + 5 == next.min
-        || (next.min <= 5 && prev.hour + 1 == next.hour)
-        || (next.hour == 0 && prev.day + 1 == next.day)
-        || (next.day == 0 && prev.month + 1 == next.month)
-    {
-        true
-    } else {
-        next.month == 0 && prev.year + 1 == next.year
+// Returns true if 'next' comes one interval after 'prev', give or take the tolerance
+fn consecutive(
+    prev: Timecode,
+    next: Timecode,
+    interval: chrono::Duration,
+    tolerance: chrono::Duration,
+) -> bool {
+    (next - prev - interval).abs() <= tolerance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::mock::png;
+    use source::mock::MockSource;
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::sync::mpsc::channel;
+    use temp_dir::TempDir;
+
+    // Config pointing at download directory 'temp'
+    fn test_config(temp: &TempDir) -> Config {
+        Config {
+            download_dir: temp.path().to_string(),
+            ..Config::default()
+        }
+    }
+
+    fn files_in(dir: &str) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn save_files_downloads_new_frames_for_each_range() {
+        let temp = TempDir::new("save_new");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3, 4]);
+        for code in site.codes() {
+            fs::create_dir(config.image_dir(code)).unwrap();
+        }
+
+        let mut source = MockSource::with_frames(&[
+            "IDR043.T.201901010000.png",
+            "IDR043.T.201901010005.png",
+            "IDR043.gif",
+            "IDR044.T.201901010000.png",
+            "IDR713.T.201901010000.png",
+        ]);
+
+        let mut index = FrameIndex::default();
+        let frames = save_files(&mut source, &mut index, &site, &config, &|_| ()).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            files_in(&config.image_dir("IDR043")),
+            vec!["IDR043.T.201901010000.png", "IDR043.T.201901010005.png"]
+        );
+        assert_eq!(
+            files_in(&config.image_dir("IDR044")),
+            vec!["IDR044.T.201901010000.png"]
+        );
+        assert_eq!(
+            fs::read(config.image_dir("IDR044") + "IDR044.T.201901010000.png").unwrap(),
+            source.frames["IDR044.T.201901010000.png"]
+        );
+        assert_eq!(index.frames("IDR043").len(), 2);
+        assert_eq!(
+            index.frames("IDR044")[0].path,
+            config.image_dir("IDR044") + "IDR044.T.201901010000.png"
+        );
+
+        // A second pass finds nothing new
+        assert!(save_files(&mut source, &mut index, &site, &config, &|_| ())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn save_files_announces_each_frame_as_it_arrives() {
+        let temp = TempDir::new("save_announce");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        fs::create_dir(config.image_dir("IDR043")).unwrap();
+
+        let mut source = MockSource::with_frames(&["IDR043.T.201901010000.png"]);
+        let events = RefCell::new(Vec::new());
+        let notify = |event| {
+            events.borrow_mut().push(match event {
+                DownloadEvent::DownloadStarted(name) => format!("started {}", name),
+                DownloadEvent::DownloadFinished(name) => format!("finished {}", name),
+                DownloadEvent::NewFrame(frame) => format!("new {}", frame.path),
+                _ => "other".to_string(),
+            })
+        };
+
+        let mut index = FrameIndex::default();
+        save_files(&mut source, &mut index, &site, &config, &notify).unwrap();
+        assert_eq!(
+            events.into_inner(),
+            vec![
+                "started IDR043.T.201901010000.png".to_string(),
+                "finished IDR043.T.201901010000.png".to_string(),
+                format!(
+                    "new {}IDR043.T.201901010000.png",
+                    config.image_dir("IDR043")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn supervisor_keeps_trying_to_start() {
+        let remote_temp = TempDir::new("supervise_remote");
+        let remote = remote_temp.join("");
+        let temp = TempDir::new("supervise");
+        let mut config = test_config(&temp);
+        config.source = "local".to_string();
+        config.local.radar_dir = remote.clone();
+        config.local.transparencies_dir = remote.clone();
+        config.timing.wait_mins = 0;
+        config.timing.retry_mins = 0;
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        fs::write(remote.clone() + "IDR043.T.201901010000.png", png()).unwrap();
+
+        let (sender, receiver) = channel();
+        let (run_site, run_config) = (site.clone(), config.clone());
+        let supervisor =
+            thread::spawn(move || run_supervised(sender, None, &run_site, &run_config, true));
+
+        // Nothing can start without the layers
+        assert!(matches!(receiver.recv().unwrap(), DownloadEvent::Error(_)));
+        for name in &["IDR043.background.png", "IDR043.locations.png"] {
+            write_atomic(&(remote.clone() + name), &png()).unwrap();
+        }
+
+        // Once they're there everything 'init' does is announced
+        let mut refreshed = false;
+        loop {
+            match receiver.recv().unwrap() {
+                DownloadEvent::BackgroundRefreshed(code) => refreshed = code == "IDR043",
+                DownloadEvent::NewFrame(frame) => {
+                    assert_eq!(frame.time, "201901010000".parse().unwrap());
+                    break;
+                }
+                _ => (),
+            }
+        }
+        assert!(refreshed);
+
+        drop(receiver);
+        assert!(supervisor.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn supervisor_stops_cleanly_while_waiting_to_restart() {
+        let temp = TempDir::new("supervise_wait");
+        let mut config = test_config(&temp);
+        config.source = "local".to_string();
+        config.local.radar_dir = temp.join("missing/");
+        config.local.transparencies_dir = temp.join("missing/");
+        config.timing.retry_mins = 1;
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+
+        let (sender, receiver) = channel();
+        let (run_site, run_config) = (site.clone(), config.clone());
+        let supervisor =
+            thread::spawn(move || run_supervised(sender, None, &run_site, &run_config, true));
+
+        // The viewer goes away part way through the wait before it retries
+        let event = receiver.recv().unwrap();
+        assert!(matches!(event, DownloadEvent::Waiting(_)));
+        drop(receiver);
+        assert!(supervisor.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn panics_are_reported_with_their_message() {
+        let panic = thread::spawn(|| panic!("bad frame {}", 3))
+            .join()
+            .unwrap_err();
+        assert_eq!(panic_message(panic), "bad frame 3");
+        let panic = thread::spawn(|| panic!("static message"))
+            .join()
+            .unwrap_err();
+        assert_eq!(panic_message(panic), "static message");
+    }
+
+    #[test]
+    fn save_files_skips_frames_already_on_disk() {
+        let temp = TempDir::new("save_existing");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        fs::create_dir(config.image_dir("IDR043")).unwrap();
+        File::create(config.image_dir("IDR043") + "IDR043.T.201901010000.png").unwrap();
+        File::create(config.image_dir("IDR043") + "xIDR043.T.201901010005.png").unwrap();
+
+        let mut source =
+            MockSource::with_frames(&["IDR043.T.201901010000.png", "IDR043.T.201901010005.png"]);
+
+        remove_legacy_prefixes(&site, &config).unwrap();
+        let mut index = FrameIndex::scan(&site, &config).unwrap();
+        assert!(save_files(&mut source, &mut index, &site, &config, &|_| ())
+            .unwrap()
+            .is_empty());
+        assert_eq!(source.fetches, 0);
+        assert_eq!(
+            files_in(&config.image_dir("IDR043")),
+            vec!["IDR043.T.201901010000.png", "IDR043.T.201901010005.png"]
+        );
+    }
+
+    #[test]
+    fn scan_keeps_legacy_names_unless_duplicated() {
+        let temp = TempDir::new("scan_legacy");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        let dir = config.image_dir("IDR043");
+        fs::create_dir(&dir).unwrap();
+        for name in &[
+            "IDR043.T.201901010000.png",
+            "xIDR043.T.201901010000.png",
+            "xIDR043.T.201901010005.png",
+        ] {
+            File::create(dir.clone() + name).unwrap();
+        }
+
+        let index = FrameIndex::scan(&site, &config).unwrap();
+        let paths: Vec<_> = index
+            .frames("IDR043")
+            .iter()
+            .map(|frame| frame.path.clone())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                dir.clone() + "IDR043.T.201901010000.png",
+                dir.clone() + "xIDR043.T.201901010005.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn consecutive_across_boundaries() {
+        let consecutive = |prev: &str, next: &str| {
+            super::consecutive(
+                prev.parse().unwrap(),
+                next.parse().unwrap(),
+                chrono::Duration::minutes(5),
+                chrono::Duration::zero(),
+            )
+        };
+
+        assert!(consecutive("201901010000", "201901010005"));
+        assert!(consecutive("201901010055", "201901010100"));
+        assert!(consecutive("201901012355", "201901020000"));
+        assert!(consecutive("201901312355", "201902010000"));
+        assert!(consecutive("201812312355", "201901010000"));
+        assert!(consecutive("202002282355", "202002290000"));
+        assert!(consecutive("202002292355", "202003010000"));
+        assert!(consecutive("201902282355", "201903010000"));
+
+        assert!(!consecutive("201901010000", "201901010010"));
+        assert!(!consecutive("201901010055", "201901010105"));
+        assert!(!consecutive("201901010000", "201901020005"));
+        assert!(!consecutive("201901010005", "201901010000"));
+    }
+
+    #[test]
+    fn consecutive_within_tolerance() {
+        let six = chrono::Duration::minutes(6);
+        let one = chrono::Duration::minutes(1);
+        let consecutive = |prev: &str, next: &str| {
+            super::consecutive(prev.parse().unwrap(), next.parse().unwrap(), six, one)
+        };
+
+        assert!(consecutive("201901010000", "201901010006"));
+        assert!(consecutive("201901010000", "201901010005"));
+        assert!(consecutive("201901010000", "201901010007"));
+        assert!(!consecutive("201901010000", "201901010004"));
+        assert!(!consecutive("201901010000", "201901010012"));
+    }
+
+    #[test]
+    fn expected_interval_prefers_config_then_median() {
+        let times: Vec<Timecode> = [
+            "201901010000",
+            "201901010010",
+            "201901010020",
+            "201901010100",
+        ]
+        .iter()
+        .map(|time| time.parse().unwrap())
+        .collect();
+        let mut config = Config::default();
+
+        assert_eq!(
+            expected_interval("IDR043", &times, &config),
+            Some(chrono::Duration::minutes(10))
+        );
+        assert_eq!(expected_interval("IDR043", &times[..1], &config), None);
+
+        config.cadence.interval_mins = 6;
+        assert_eq!(
+            expected_interval("IDR043", &times, &config),
+            Some(chrono::Duration::minutes(6))
+        );
+
+        config.cadence.sites.insert("04".to_string(), 5);
+        assert_eq!(
+            expected_interval("IDR043", &times, &config),
+            Some(chrono::Duration::minutes(5))
+        );
+        assert_eq!(
+            expected_interval("IDR713", &times, &config),
+            Some(chrono::Duration::minutes(6))
+        );
+    }
+
+    #[test]
+    fn clean_keeps_ten_minute_history() {
+        let temp = TempDir::new("clean_ten");
+        let config = test_config(&temp);
+        let site = RadarSite::new("02", "Melbourne", &[3]);
+        let dir = config.image_dir("IDR023");
+        fs::create_dir(&dir).unwrap();
+        for time in &[
+            "201901010000",
+            "201901010030",
+            "201901010040",
+            "201901010050",
+        ] {
+            File::create(dir.clone() + "IDR023.T." + time + ".png").unwrap();
+        }
+
+        clean(&site, &config).unwrap();
+
+        assert_eq!(
+            files_in(&dir),
+            vec![
+                "IDR023.T.201901010030.png",
+                "IDR023.T.201901010040.png",
+                "IDR023.T.201901010050.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn clean_keeps_streaks_across_month_boundaries() {
+        let temp = TempDir::new("clean");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        let dir = config.image_dir("IDR043");
+        fs::create_dir(&dir).unwrap();
+        for time in &[
+            "201901312340",
+            "201901312350",
+            "201901312355",
+            "201902010000",
+        ] {
+            File::create(dir.clone() + "IDR043.T." + time + ".png").unwrap();
+        }
+
+        clean(&site, &config).unwrap();
+
+        assert_eq!(
+            files_in(&dir),
+            vec![
+                "IDR043.T.201901312350.png",
+                "IDR043.T.201901312355.png",
+                "IDR043.T.201902010000.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn clean_leaves_partial_files_and_quarantine_alone() {
+        let temp = TempDir::new("clean_partial");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        let dir = config.image_dir("IDR043");
+        fs::create_dir(&dir).unwrap();
+        for time in &[
+            "201901010000",
+            "201901010030",
+            "201901010035",
+            "201901010040",
+        ] {
+            File::create(dir.clone() + "IDR043.T." + time + ".png").unwrap();
+        }
+        File::create(dir.clone() + ".IDR043.T.201901010045.png.part").unwrap();
+
+        // Quarantined frames aren't a range, so they're never cleaned
+        let quarantine = config.download_dir.clone() + "/quarantine/";
+        fs::create_dir(&quarantine).unwrap();
+        for time in &["201901010000", "201901010030"] {
+            File::create(quarantine.clone() + "IDR043.T." + time + ".png").unwrap();
+        }
+
+        clean(&site, &config).unwrap();
+
+        assert_eq!(
+            files_in(&dir),
+            vec![
+                ".IDR043.T.201901010045.png.part",
+                "IDR043.T.201901010030.png",
+                "IDR043.T.201901010035.png",
+                "IDR043.T.201901010040.png"
+            ]
+        );
+        assert_eq!(files_in(&quarantine).len(), 2);
+    }
+
+    #[test]
+    fn save_files_rejects_corrupt_frames() {
+        let temp = TempDir::new("save_corrupt");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        fs::create_dir(config.image_dir("IDR043")).unwrap();
+
+        let mut source = MockSource::with_frames(&["IDR043.T.201901010000.png"]);
+        let good = source.frames["IDR043.T.201901010000.png"].clone();
+        source.add_frame("IDR043.T.201901010005.png", &good[..good.len() / 2]);
+
+        let mut index = FrameIndex::default();
+        let frames = save_files(&mut source, &mut index, &site, &config, &|_| ()).unwrap();
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            files_in(&config.image_dir("IDR043")),
+            vec!["IDR043.T.201901010000.png"]
+        );
+        assert!(!index.contains("IDR043", "201901010005".parse().unwrap()));
+    }
+
+    #[test]
+    fn corrupt_frames_on_disk_are_quarantined() {
+        let temp = TempDir::new("quarantine");
+        let config = test_config(&temp);
+        let site = RadarSite::new("04", "Newcastle", &[3]);
+        let dir = config.image_dir("IDR043");
+        fs::create_dir(&dir).unwrap();
+        let good = png();
+        fs::write(dir.clone() + "IDR043.T.201901010000.png", &good).unwrap();
+        fs::write(dir.clone() + "IDR043.T.201901010005.png", &good[..10]).unwrap();
+        fs::write(dir.clone() + ".IDR043.T.201901010010.png.part", &good[..10]).unwrap();
+
+        let mut index = FrameIndex::scan(&site, &config).unwrap();
+        let corrupt = quarantine_corrupt(&mut index, &site.codes(), &config).unwrap();
+
+        assert_eq!(corrupt, vec![dir.clone() + "IDR043.T.201901010005.png"]);
+        assert_eq!(files_in(&dir), vec!["IDR043.T.201901010000.png"]);
+        assert_eq!(index.frames("IDR043").len(), 1);
+        assert_eq!(
+            files_in(&(config.download_dir.clone() + "/quarantine/")),
+            vec!["IDR043.T.201901010005.png"]
+        );
     }
 }
@@ -0,0 +1,64 @@
+use ftp::FtpError;
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+// Errors from anywhere in the program. Everything here is Send so errors from the download
+// thread can be passed to the viewer.
+#[derive(Debug)]
+pub enum Error {
+    Ftp(FtpError),
+    Http(Box<ureq::Error>),
+    Io(io::Error),
+    Parse(String),   // Unexpected file name or config contents
+    Decode(String),  // Image that couldn't be read, with the reason
+    Render(String),  // OpenGL errors, or a rendered image that couldn't be saved
+    Disconnected,    // The other end of a channel has gone away
+    Crashed(String), // A thread panicked, with the panic message
+    Locked(String),  // Another collector is using the download folder
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Ftp(e) => write!(f, "FTP error: {}", e),
+            Error::Http(e) => write!(f, "HTTP error: {}", e),
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Parse(e) => write!(f, "Parse error: {}", e),
+            Error::Decode(e) => write!(f, "Decode error: {}", e),
+            Error::Render(e) => write!(f, "Render error: {}", e),
+            Error::Disconnected => write!(f, "Channel disconnected"),
+            Error::Crashed(e) => write!(f, "Thread crashed: {}", e),
+            Error::Locked(e) => write!(f, "Download folder locked: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<FtpError> for Error {
+    fn from(e: FtpError) -> Self {
+        Error::Ftp(e)
+    }
+}
+
+impl From<ureq::Error> for Error {
+    fn from(e: ureq::Error) -> Self {
+        Error::Http(Box::new(e))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+// Decode error for image 'path'. Image errors don't say which file they came from so they're
+// converted with this instead of a From impl
+pub fn decode_error<E: fmt::Display>(path: &str, e: E) -> Error {
+    Error::Decode(format!("{}: {}", path, e))
+}
@@ -0,0 +1,16 @@
+use error::Error;
+use frame_index::Frame;
+
+// Messages from the download thread to the viewer
+pub enum DownloadEvent {
+    Waiting(f32), // Progress through the wait before the next download, 0.0 -> 1.0
+    Checking,     // Asking the source for new frames
+    Watching,     // Not downloading, just watching the folder for frames saved by another process
+    DownloadStarted(String), // File name of a frame being downloaded
+    DownloadFinished(String), // File name of a frame that has been saved
+    NewFrame(Frame), // A frame has been completely downloaded and can be shown
+    Pruned(Vec<String>), // Paths of frames removed by the retention policy
+    BackgroundRefreshed(String), // Product code whose background and location layers were replaced
+    Error(Error), // A download failed, it will be retried
+    ServerUnreachable(Error), // Couldn't connect to the source at all, it will be retried
+}
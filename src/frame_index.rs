@@ -0,0 +1,113 @@
+// Index of the complete radar frames on disk.
+// The downloader adds frames once they're fully written and announces them to the viewer, so
+// nothing ever needs to guess from the file system whether a file is finished.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use config::Config;
+use downloader::file_names_in;
+use error::Result;
+use radar::RadarSite;
+use timecode::Timecode;
+
+// Prefix older versions used to mark files the viewer hadn't loaded yet
+const LEGACY_NEW_PREFIX: char = 'x';
+
+// A single radar image on disk
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub lc_code: String,
+    pub time: Timecode,
+    pub path: String,
+}
+
+#[derive(Default)]
+pub struct FrameIndex {
+    frames: HashMap<String, BTreeMap<Timecode, Frame>>, // Product code -> frames in time order
+}
+
+impl FrameIndex {
+    // Build the index from the images already on disk for each range of 'site'.
+    // Files that aren't BOM radar images are ignored, as are ranges without a folder yet.
+    // Files still named with the legacy prefix are indexed under that name unless the BOM name is
+    // there too, as nothing renames them when viewing offline.
+    pub fn scan(site: &RadarSite, config: &Config) -> Result<Self> {
+        let mut index = FrameIndex::default();
+
+        for lc_code in site.codes() {
+            let dir = config.image_dir(lc_code);
+            if !Path::new(&dir).is_dir() {
+                continue;
+            }
+            for name in file_names_in(&dir)? {
+                let (bom_name, legacy) = match name.strip_prefix(LEGACY_NEW_PREFIX) {
+                    Some(bom_name) => (bom_name, true),
+                    None => (name.as_str(), false),
+                };
+                if let Ok(time) = Timecode::from_file_name(bom_name) {
+                    if bom_name.starts_with(lc_code) && !(legacy && index.contains(lc_code, time)) {
+                        index.insert(Frame {
+                            lc_code: lc_code.to_string(),
+                            time,
+                            path: dir.clone() + &name,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub fn insert(&mut self, frame: Frame) {
+        self.frames
+            .entry(frame.lc_code.clone())
+            .or_default()
+            .insert(frame.time, frame);
+    }
+
+    pub fn contains(&self, lc_code: &str, time: Timecode) -> bool {
+        self.frames
+            .get(lc_code)
+            .is_some_and(|frames| frames.contains_key(&time))
+    }
+
+    // Forget the frames at 'paths', e.g. after they've been deleted
+    pub fn remove_paths(&mut self, paths: &[String]) {
+        for frames in self.frames.values_mut() {
+            frames.retain(|_, frame| !paths.contains(&frame.path));
+        }
+    }
+
+    // Frames for product 'lc_code', oldest first
+    pub fn frames(&self, lc_code: &str) -> Vec<&Frame> {
+        self.frames
+            .get(lc_code)
+            .map(|frames| frames.values().collect())
+            .unwrap_or_default()
+    }
+}
+
+// Rename files left with the old 'x' new file prefix back to their BOM names.
+// If both names exist the prefixed copy is a duplicate and is removed.
+pub fn remove_legacy_prefixes(site: &RadarSite, config: &Config) -> Result<()> {
+    for lc_code in site.codes() {
+        let dir = config.image_dir(lc_code);
+        for name in file_names_in(&dir)? {
+            let bom_name = match name.strip_prefix(LEGACY_NEW_PREFIX) {
+                Some(bom_name) if bom_name.starts_with(lc_code) => bom_name,
+                _ => continue,
+            };
+
+            if fs::metadata(dir.clone() + bom_name).is_ok() {
+                fs::remove_file(dir.clone() + &name)?;
+            } else {
+                fs::rename(dir.clone() + &name, dir.clone() + bom_name)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,14 @@
 use std::sync::mpsc::Receiver;
 use super::glium;
-use glium::DrawError;
 
 use glium::glutin::event::ElementState;
 use glium::glutin::event::KeyboardInput;
+use glium::glutin::event::ModifiersState;
+use glium::glutin::event::MouseButton;
 use glium::glutin::event::VirtualKeyCode as Key;
 use glium::glutin::event::WindowEvent;
 use glium::glutin::event_loop::ControlFlow;
+use glium::glutin::platform::run_return::EventLoopExtRunReturn;
 
 use std::fs;
 use std::iter::Iterator;
@@ -14,45 +16,130 @@ use std::str;
 use std::time::Duration;
 use std::time::Instant;
 
+mod compositor;
+mod decoder;
+mod export;
+mod font;
+mod layout;
+mod overlay;
+mod playback;
 mod renderable;
 mod renderer;
+mod status;
+mod texture_cache;
+use config::Config;
+use config::SpeedConfig;
+use error::Result;
+use event::DownloadEvent;
+use frame_index::Frame;
+use frame_index::FrameIndex;
+use image_viewer::compositor::Compositor;
+use image_viewer::decoder::Decoder;
+use image_viewer::export::AnimationWriter;
+use image_viewer::layout::DEFAULT_SIZE;
+use image_viewer::overlay::OverlayInfo;
 use image_viewer::renderable::Renderable;
 use image_viewer::renderable::RenderableType;
 use image_viewer::renderer::Renderer;
+use image_viewer::status::DownloadStatus;
+use radar::RadarSite;
+use timecode::Timecode;
 
-use super::CODE_HIGH;
-use super::CODE_LOW;
-use super::CODE_MID;
-use super::DL_DIR;
-use super::SPEED_FAST;
-use super::SPEED_MID;
-use super::SPEED_SLOW;
+// Time between redraws while fading between frames
+const FADE_STEP: Duration = Duration::from_millis(16);
 
-// Opens a new window, displaying only the files that currently exist in img
-pub fn open_window(receiver: Receiver<f32>) -> Result<(), DrawError> {
+// Opens a new window, displaying the files that currently exist in img and adding frames as the
+// downloader announces them. The downloader's status is shown in the window title.
+// Fails if the window or the background images can't be loaded, unless it's a kiosk or 'offline'.
+// Once the window is open errors are reported and the offending image is dropped. Returns when the
+// window is closed
+pub fn open_window(
+    receiver: Receiver<DownloadEvent>,
+    site: &RadarSite,
+    config: &Config,
+    offline: bool,
+) -> Result<()> {
+    let site = site.clone();
+    let config = config.clone();
     let mut index = 0;
-    let mut zoom = 1;
-    let mut frame_time = SPEED_MID;
+    let mut zoom = site.default_range();
+    let mut fps = config.speeds.fps;
+    let mut window_hours = config.loop_window.hours;
 
     // Do a bunch of init garbage
-    let (mut renderer, events_loop) = Renderer::new();
-    let (mut bg_renderables, mut lc_renderables) = background_init();
-    let mut renderables = create_all_renderables_from_files();
-    let mut upper_ui = Renderable::from_disk_image("blue.jpg", RenderableType::UpperUI);
-    let mut bottom_ui = Renderable::from_disk_image("salmon.png", RenderableType::BottomUI);
-    let frame_time_nano = (frame_time * 1000000) as u64;
-    let mut next_frame_time = Instant::now() + Duration::from_nanos(frame_time_nano);
+    let (mut renderer, mut events_loop) =
+        Renderer::new(config.cache.max_megabytes * 1024 * 1024, &config.kiosk)?;
+    let (bg_renderables, lc_renderables) = background_init(&site, &config);
+    let mut renderables = create_all_renderables_from_files(&site, &config)?;
+    let upper_ui = Renderable::from_disk_image("blue.jpg", RenderableType::UpperUI);
+    let bottom_ui = Renderable::from_disk_image("salmon.png", RenderableType::BottomUI);
+    let mut decoder = Decoder::new(config.cache.decode_threads);
 
-    // This file should probably think about the time, and the renderable itself should know how to make that
-    // an offset..
-    let mut timer_progress = 0.0;
+    // Load the textures that are drawn every frame now, so a missing file is reported up front
+    // rather than on every frame. A kiosk, or a viewer watching another process's downloads, opens
+    // without them, they're loaded once they've been fetched
+    for item in bg_renderables.iter().chain(lc_renderables.iter()) {
+        match renderer.load(item) {
+            Err(e) if config.kiosk.enabled || offline => println!("{}", e),
+            result => result?,
+        }
+    }
+    renderer.load(&upper_ui)?;
+    renderer.load(&bottom_ui)?;
+    let mut next_frame_time = Instant::now() + frame_duration(fps);
+
+    let mut status = DownloadStatus::new();
+    let mut title = String::new();
+
+    let mut paused = false;
+    let mut redraw = false; // Draw straight away rather than waiting for the next frame
+    let mut cursor = (0.0, 0.0); // Mouse position in pixels from the top left
+    let mut scrubbing = false; // Dragging along the upper progress bar
+    let mut modifiers = ModifiersState::empty(); // Modifier keys currently held
+    let mut shown: Option<Renderable> = None; // Last radar frame drawn, held while the next decodes
+
+    events_loop.run_return(move |ev, _, control_flow| {
+        // Handle everything the downloader has sent since last time
+        while let Ok(event) = receiver.try_recv() {
+            status.update(&event);
+            match event {
+                DownloadEvent::NewFrame(frame) => {
+                    index = add_new_renderable(&mut renderables, &frame, &site, zoom, index)
+                }
+                DownloadEvent::Pruned(paths) => {
+                    index = remove_renderables(&mut renderables, &paths, zoom, index);
+                    for path in &paths {
+                        renderer.forget(path);
+                    }
+                }
+                DownloadEvent::BackgroundRefreshed(code) => {
+                    if let Some(zoom) = site.codes().iter().position(|c| *c == code) {
+                        for item in &[&bg_renderables[zoom], &lc_renderables[zoom]] {
+                            renderer.forget(&item.img);
+                            decoder.retry(&item.img);
+                        }
+                    }
+                }
+                DownloadEvent::Error(e) | DownloadEvent::ServerUnreachable(e) => {
+                    println!("\rDownloader reported: {}", e)
+                }
+                _ => (),
+            }
+        }
 
-    events_loop.run(move |ev, _, control_flow| {
-        // Check channel for update
-        if let Ok(timer) = receiver.try_recv(){
-            timer_progress = timer;
+        let description = format!("Radar Monitor - {} - {}", site.name, status.describe());
+        if description != title {
+            renderer.set_title(&description);
+            title = description;
         }
 
+        // Only the frames in the loop window are played
+        let mut frames = playback::loop_range(
+            &renderables[zoom],
+            window_hours,
+            config.loop_window.max_frames,
+        );
+
 
         if let glium::glutin::event::Event::WindowEvent { event, .. } = ev {
             match event {
@@ -60,6 +147,11 @@ pub fn open_window(receiver: Receiver<f32>) -> Result<(), DrawError> {
                     *control_flow = ControlFlow::Exit;
                     return;
                 }
+                WindowEvent::Resized(size) => {
+                    renderer.resize(size);
+                    redraw = true;
+                }
+                WindowEvent::ModifiersChanged(state) => modifiers = state,
                 WindowEvent::KeyboardInput {
                     input:
                         KeyboardInput {
@@ -69,163 +161,574 @@ pub fn open_window(receiver: Receiver<f32>) -> Result<(), DrawError> {
                         },
                     ..
                 } => match key {
-                    Key::Down => frame_time = change_speed(frame_time, false),
-                    Key::Up => frame_time = change_speed(frame_time, true),
-                    Key::LBracket | Key::End => {
-                        zoom = change_zoom(zoom, false);
-                        if renderables[zoom].len() <= index {
-                            index = 0;
-                        };
+                    Key::Down => fps = change_speed(fps, false, &config.speeds),
+                    Key::Up => fps = change_speed(fps, true, &config.speeds),
+                    Key::LBracket | Key::End => zoom = change_zoom(zoom, false, &site),
+                    Key::RBracket | Key::Home => zoom = change_zoom(zoom, true, &site),
+                    Key::Period | Key::Comma => {
+                        window_hours = playback::change_window(
+                            window_hours,
+                            key == Key::Period,
+                            &config.loop_window,
+                        );
+                        redraw = true;
                     }
-                    Key::RBracket | Key::Home => {
-                        zoom = change_zoom(zoom, true);
-                        if renderables[zoom].len() <= index {
-                            index = 0;
-                        }
+                    Key::Space => {
+                        paused = !paused;
+                        redraw = true;
                     }
-                    Key::Escape | Key::Q => {
+                    Key::Left | Key::Right => {
+                        index = playback::step(index, &frames, key == Key::Right);
+                        paused = true;
+                        redraw = true;
+                    }
+                    Key::PageUp => {
+                        index = frames.start;
+                        redraw = true;
+                    }
+                    Key::PageDown => {
+                        index = playback::newest(&frames);
+                        redraw = true;
+                    }
+                    // A kiosk shouldn't be closed by a stray key press, so there Ctrl has to be held
+                    Key::Escape | Key::Q if !config.kiosk.enabled || modifiers.ctrl() => {
                         *control_flow = ControlFlow::Exit;
                         return;
                     }
                     _ => (),
                 },
+                WindowEvent::CursorMoved { position, .. } => {
+                    cursor = (position.x as f32, position.y as f32);
+                    if scrubbing {
+                        let fraction = renderer.layout().fraction_across(cursor.0);
+                        index = playback::scrub(fraction, &frames);
+                        redraw = true;
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    let layout = renderer.layout();
+                    scrubbing = state == ElementState::Pressed
+                        && (scrubbing || layout.contains_row(RenderableType::UpperUI, cursor.1));
+                    if scrubbing {
+                        index = playback::scrub(layout.fraction_across(cursor.0), &frames);
+                        paused = true;
+                        redraw = true;
+                    }
+                }
                 _ => {}
             }
         }
 
-        if Instant::now() < next_frame_time {
-            *control_flow = ControlFlow::WaitUntil(next_frame_time);
+        // Between frames the screen is redrawn while fading into the next one
+        let now = Instant::now();
+        let fade = frame_duration(fps).mul_f32(config.speeds.crossfade.clamp(0.0, 1.0));
+        let blend = if paused {
+            0.0
+        } else {
+            fade_amount(now, next_frame_time, fade)
+        };
+
+        let due = now >= next_frame_time;
+        if !due && !redraw && blend == 0.0 {
+            *control_flow = ControlFlow::WaitUntil(wake_time(now, next_frame_time, fade, paused));
             return;
         }
+        let fading = !due && !redraw;
+        redraw = false;
+
+        // The zoom or window may have changed, and the current frame may now be outside the loop
+        frames = playback::loop_range(
+            &renderables[zoom],
+            window_hours,
+            config.loop_window.max_frames,
+        );
+        if !frames.contains(&index) {
+            index = frames.start;
+        }
 
-        let frame_time_nano = (frame_time * 1000000) as u64;
-        next_frame_time = Instant::now() + Duration::from_nanos(frame_time_nano);
+        if due {
+            next_frame_time = Instant::now() + frame_duration(fps);
+            // Linger on the newest frame before looping back to the oldest
+            if !paused && index + 1 >= frames.end {
+                next_frame_time += Duration::from_millis(config.speeds.hold_latest_ms);
+            }
+        }
 
-        *control_flow = ControlFlow::WaitUntil(next_frame_time);
+        *control_flow = ControlFlow::WaitUntil(wake_time(now, next_frame_time, fade, paused));
+
+        // Upload the images decoded by the workers since last time. A frame that can't be
+        // decoded (e.g. a corrupt download) is dropped from the loop
+        for (path, result) in decoder.finished() {
+            match result {
+                Ok(image) => report(renderer.upload(&path, image)),
+                Err(e) if renderables.iter().flatten().any(|r| r.img == path) => {
+                    println!("\rDropping frame: {}", e);
+                    index = remove_renderables(&mut renderables, &[path], zoom, index);
+                }
+                Err(e) => println!("\r{}", e),
+            }
+        }
+        frames = playback::loop_range(
+            &renderables[zoom],
+            window_hours,
+            config.loop_window.max_frames,
+        );
 
         renderer.new_frame();
         // Draw the background, then map overlay, then radar data
-        renderer.draw(&mut bg_renderables[zoom]);
-        renderer.draw(&mut lc_renderables[zoom]);
-        renderer.draw(&mut renderables[zoom][index]);
+        for item in &[&bg_renderables[zoom], &lc_renderables[zoom]] {
+            if is_ready(&renderer, &mut decoder, item) {
+                report(renderer.draw(item));
+            }
+        }
 
-        // Calculate our progress through the image set
-        let end = renderables[zoom].len();
-        let images_progress = index as f32 / end as f32;
+        // 'index' is the next frame to show. If it isn't decoded yet keep showing the last one,
+        // and wait for it
+        let current = renderables[zoom].get(index);
+        let mut ready = true;
+        if fading {
+            // Fade forwards only, not back to the start of the loop
+            let next = current.filter(|next| {
+                shown.as_ref().is_some_and(|item| item.time < next.time)
+                    && is_ready(&renderer, &mut decoder, next)
+            });
+            if let (Some(item), Some(next)) = (&shown, next) {
+                if is_ready(&renderer, &mut decoder, item) {
+                    report(renderer.draw_blended(item, next, blend));
+                }
+            } else if let Some(item) = &shown {
+                if is_ready(&renderer, &mut decoder, item) {
+                    report(renderer.draw(item));
+                }
+            }
+        } else {
+            ready = current.is_none_or(|item| is_ready(&renderer, &mut decoder, item));
+            if ready {
+                shown = current.cloned();
+            }
+            if let Some(item) = &shown {
+                if is_ready(&renderer, &mut decoder, item) {
+                    report(renderer.draw(item));
+                }
+            }
+        }
 
-        renderer.draw_progress_bar(&mut upper_ui, images_progress);
-        renderer.draw_progress_bar(&mut bottom_ui, timer_progress);
+        // Calculate our progress through the loop
+        let end = frames.len().max(1);
+        let images_progress = index.saturating_sub(frames.start) as f32 / end as f32;
 
-        renderer.finish_frame();
+        if is_ready(&renderer, &mut decoder, &upper_ui) {
+            report(renderer.draw_progress_bar(&upper_ui, images_progress));
+        }
+        if is_ready(&renderer, &mut decoder, &bottom_ui) {
+            report(renderer.draw_progress_bar(&bottom_ui, status.timer_progress()));
+        }
 
-        // Next image (with wraparound)
-        index = {
-            if index + 1 < renderables[zoom].len() {
-                index + 1
-            } else {
-                0
-            }
+        let info = OverlayInfo {
+            site: &site,
+            zoom,
+            frame_time: shown.as_ref().and_then(|r| r.time),
+            index: index.saturating_sub(frames.start),
+            count: frames.len(),
+            window_hours,
+            fps,
+            paused,
+            status: status.describe(),
         };
+        let radar = renderer.layout().rect(RenderableType::MainImage);
+        report(overlay::draw(&mut renderer, &info, radar));
+
+        report(renderer.finish_frame());
 
-        // Check for new images if we just wrapped around
-        if index == 0 {
-            add_all_new_renderables(&mut renderables);
+        // Next image (with wraparound), unless this was an early redraw
+        if due && !paused && ready {
+            index = playback::step(index, &frames, true);
         }
-    })
+
+        // Get the next few frames ready so playback doesn't wait on the disk
+        let mut next = index;
+        for _ in 0..config.cache.predecode_frames.min(frames.len()) {
+            next = playback::step(next, &frames, true);
+            if let Some(item) = renderables[zoom].get(next) {
+                is_ready(&renderer, &mut decoder, item);
+            }
+        }
+    });
+    Ok(())
 }
 
-fn change_zoom(zoom: usize, faster: bool) -> usize {
-    if faster {
-        if zoom == 0 {
-            1
-        } else {
-            2
+// Draw the newest frame on disk for range 'zoom' the way the window would, at its default size,
+// and save it to 'path' as a PNG. Needs no display, so it works from a cron job alongside the
+// downloader
+pub fn render_to_png(site: &RadarSite, config: &Config, zoom: usize, path: &str) -> Result<()> {
+    let (bg_renderables, lc_renderables) = background_init(site, config);
+    let renderables = create_all_renderables_from_files(site, config)?;
+    let upper_ui = Renderable::from_disk_image("blue.jpg", RenderableType::UpperUI);
+    let frames = playback::loop_range(
+        &renderables[zoom],
+        config.loop_window.hours,
+        config.loop_window.max_frames,
+    );
+    let index = playback::newest(&frames);
+    let current = renderables[zoom].get(index);
+
+    let mut compositor = Compositor::new(DEFAULT_SIZE, config.cache.max_megabytes * 1024 * 1024);
+    compositor.draw(&bg_renderables[zoom])?;
+    compositor.draw(&lc_renderables[zoom])?;
+    if let Some(item) = current {
+        compositor.draw(item)?;
+    }
+
+    // The lower bar counts down to the next download, which there isn't one of here
+    let progress = index.saturating_sub(frames.start) as f32 / frames.len().max(1) as f32;
+    compositor.draw_progress_bar(&upper_ui, progress)?;
+
+    let info = OverlayInfo {
+        site,
+        zoom,
+        frame_time: current.and_then(|r| r.time),
+        index: index.saturating_sub(frames.start),
+        count: frames.len(),
+        window_hours: config.loop_window.hours,
+        fps: config.speeds.fps,
+        paused: true,
+        status: String::new(),
+    };
+    let radar = compositor.layout().rect(RenderableType::MainImage);
+    overlay::draw(&mut compositor, &info, radar)?;
+
+    compositor.save(path)
+}
+
+// Write the frames on disk for range 'zoom' taken between 'from' and 'to' (the viewer's loop
+// window if neither is given) to an animated GIF or PNG at 'path', each over the background and
+// locations layers and shown for 'delay' (the viewer's speed if not given). The last frame is held
+// for the usual extra time before looping. Returns the number of frames written, no file is
+// written if there are none and it's removed again if writing fails part way
+pub fn export_animation(
+    site: &RadarSite,
+    config: &Config,
+    zoom: usize,
+    from: Option<Timecode>,
+    to: Option<Timecode>,
+    delay: Option<Duration>,
+    path: &str,
+) -> Result<usize> {
+    let delay = delay.unwrap_or_else(|| frame_duration(config.speeds.fps));
+    let (bg_renderables, lc_renderables) = background_init(site, config);
+    let renderables = create_all_renderables_from_files(site, config)?;
+    let frames = if from.is_none() && to.is_none() {
+        playback::loop_range(
+            &renderables[zoom],
+            config.loop_window.hours,
+            config.loop_window.max_frames,
+        )
+    } else {
+        playback::time_range(&renderables[zoom], from, to)
+    };
+    if frames.is_empty() {
+        return Ok(0);
+    }
+
+    let mut compositor = Compositor::new(DEFAULT_SIZE, config.cache.max_megabytes * 1024 * 1024);
+    let radar = compositor.layout().rect(RenderableType::MainImage);
+    let mut writer = AnimationWriter::create(path, compositor.radar_size(), frames.len())?;
+
+    let written = (|| {
+        for index in frames.clone() {
+            let item = &renderables[zoom][index];
+            compositor.new_frame();
+            compositor.draw(&bg_renderables[zoom])?;
+            compositor.draw(&lc_renderables[zoom])?;
+            compositor.draw(item)?;
+            overlay::draw_time(&mut compositor, item.time, radar)?;
+
+            let delay = if index + 1 == frames.end {
+                delay + Duration::from_millis(config.speeds.hold_latest_ms)
+            } else {
+                delay
+            };
+            writer.add_frame(compositor.radar_image(), delay)?;
         }
-    } else if zoom == 2 {
-        1
+        writer.finish()
+    })();
+    if written.is_err() {
+        // Don't leave a file that looks like an animation but is cut short
+        let _ = fs::remove_file(path);
+    }
+    written?;
+
+    Ok(frames.len())
+}
+
+// True if the texture for 'item' is loaded, otherwise asks for it to be decoded
+fn is_ready(renderer: &Renderer, decoder: &mut Decoder, item: &Renderable) -> bool {
+    if renderer.is_loaded(&item.img) {
+        true
     } else {
-        0
+        decoder.request(&item.img);
+        false
     }
 }
 
-fn change_speed(current: usize, increase: bool) -> usize {
-    let value = if increase {
-        if current == SPEED_SLOW {
-            SPEED_MID
+// Print an error from inside the event loop, where there's no caller to return it to
+fn report(result: Result<()>) {
+    if let Err(e) = result {
+        println!("\r{}", e);
+    }
+}
+
+// Step the zoom level in or out, clamped to the number of ranges the site has
+fn change_zoom(zoom: usize, zoom_in: bool, site: &RadarSite) -> usize {
+    let value = if zoom_in {
+        if zoom + 1 < site.ranges.len() {
+            zoom + 1
         } else {
-            SPEED_FAST
+            zoom
         }
-    } else if current == SPEED_FAST {
-        SPEED_MID
+    } else if zoom > 0 {
+        zoom - 1
     } else {
-        SPEED_SLOW
+        0
     };
-    println!("Frame Time = {}", value);
+    println!("Range = {}km", site.ranges[value].km);
     value
 }
 
-// Create background and location texture arrays. Just to clean up init in main function
-fn background_init() -> ([Renderable; 3], [Renderable; 3]) {
-    // What is formatting
-    (
-        [
-            Renderable::from_disk_image(&(CODE_LOW.to_string() + ".background.png"), RenderableType::MainImage),
-            Renderable::from_disk_image(&(CODE_MID.to_string() + ".background.png"), RenderableType::MainImage),
-            Renderable::from_disk_image(&(CODE_HIGH.to_string() + ".background.png"), RenderableType::MainImage),
-        ],
-        [
-            Renderable::from_disk_image(&(CODE_LOW.to_string() + ".locations.png"), RenderableType::MainImage),
-            Renderable::from_disk_image(&(CODE_MID.to_string() + ".locations.png"), RenderableType::MainImage),
-            Renderable::from_disk_image(&(CODE_HIGH.to_string() + ".locations.png"), RenderableType::MainImage),
-        ],
-    )
+// Step the speed up or down by one step, clamped to the configured range
+fn change_speed(fps: f32, faster: bool, speeds: &SpeedConfig) -> f32 {
+    let value = if faster {
+        fps + speeds.step_fps
+    } else {
+        fps - speeds.step_fps
+    };
+    let value = value.max(speeds.min_fps).min(speeds.max_fps);
+    println!("Speed = {} fps", value);
+    value
 }
 
-fn create_all_renderables_from_files() -> [Vec<Renderable>; 3] {
-    [
-        Renderable::from_location_folder(CODE_LOW),
-        Renderable::from_location_folder(CODE_MID),
-        Renderable::from_location_folder(CODE_HIGH),
-    ]
+// Time to show each frame for at 'fps' frames per second
+fn frame_duration(fps: f32) -> Duration {
+    Duration::from_secs_f32(1.0 / fps.max(0.01))
 }
 
-fn add_all_new_renderables(vecs: &mut [Vec<Renderable>; 3]) {
-    add_new_renderables(&mut vecs[0], CODE_LOW);
-    add_new_renderables(&mut vecs[1], CODE_MID);
-    add_new_renderables(&mut vecs[2], CODE_HIGH);
+// How far through fading into the next frame we are, 0.0 -> 1.0. The fade takes the last 'fade'
+// of the time before 'next_frame_time'
+fn fade_amount(now: Instant, next_frame_time: Instant, fade: Duration) -> f32 {
+    let remaining = next_frame_time.saturating_duration_since(now);
+    if fade.is_zero() || remaining >= fade {
+        0.0
+    } else {
+        1.0 - remaining.as_secs_f32() / fade.as_secs_f32()
+    }
 }
 
-fn add_new_renderables(vec: &mut Vec<Renderable>, lc_code: &str) {
-    let dir = &(DL_DIR.to_string() + lc_code + "/");
-    let files = fs::read_dir(dir).expect("Error reading image directory");
+// When the event loop next needs to wake up: at the next frame, or often enough to animate the
+// fade into it
+fn wake_time(now: Instant, next_frame_time: Instant, fade: Duration, paused: bool) -> Instant {
+    if paused || fade.is_zero() {
+        return next_frame_time;
+    }
+
+    let fade_start = next_frame_time.checked_sub(fade).unwrap_or(now);
+    if now < fade_start {
+        fade_start
+    } else {
+        (now + FADE_STEP).min(next_frame_time)
+    }
+}
+
+// Create background and location textures for each range. Just to clean up init in main function
+fn background_init(site: &RadarSite, config: &Config) -> (Vec<Renderable>, Vec<Renderable>) {
+    let layer = |suffix: &str| {
+        site.codes()
+            .iter()
+            .map(|code| config.layer_path(&(code.to_string() + suffix)))
+            .map(|path| Renderable::from_disk_image(&path, RenderableType::MainImage))
+            .collect()
+    };
+
+    (layer(".background.png"), layer(".locations.png"))
+}
 
-    let file_names: Vec<_> = files
-        .map(|e| {
-            e.expect("Error reading image filename")
-                .file_name()
-                .into_string()
-                .expect("Error extracting image filename")
+fn create_all_renderables_from_files(
+    site: &RadarSite,
+    config: &Config,
+) -> Result<Vec<Vec<Renderable>>> {
+    let index = FrameIndex::scan(site, config)?;
+    Ok(site
+        .codes()
+        .iter()
+        .map(|code| {
+            index
+                .frames(code)
+                .into_iter()
+                .map(Renderable::from_frame)
+                .collect()
         })
-        .collect();
+        .collect())
+}
+
+// Add 'frame' to the renderables for its zoom level, in time order. Frames that are already
+// loaded (found by the initial scan as well as announced) are skipped.
+// Returns the index of the frame that was showing at the current zoom level
+fn add_new_renderable(
+    vecs: &mut [Vec<Renderable>],
+    frame: &Frame,
+    site: &RadarSite,
+    zoom: usize,
+    index: usize,
+) -> usize {
+    let frame_zoom = match site.codes().iter().position(|code| *code == frame.lc_code) {
+        Some(frame_zoom) => frame_zoom,
+        None => return index,
+    };
 
-    let mut file_names = file_names
+    // BOM file names sort in time order
+    let vec = &mut vecs[frame_zoom];
+    match vec.binary_search_by(|r| r.img.as_str().cmp(&frame.path)) {
+        Ok(_) => index,
+        Err(position) => {
+            vec.insert(position, Renderable::from_frame(frame));
+            if frame_zoom == zoom && position <= index && vec.len() > 1 {
+                index + 1
+            } else {
+                index
+            }
+        }
+    }
+}
+
+// Remove the renderables for the images at 'paths'.
+// Returns the index of the frame that was showing at the current zoom level, or the start of the
+// loop if it was removed
+fn remove_renderables(
+    vecs: &mut [Vec<Renderable>],
+    paths: &[String],
+    zoom: usize,
+    index: usize,
+) -> usize {
+    let removed_before = vecs[zoom]
         .iter()
-        .filter(|e| e.starts_with('x'))
-        .collect::<Vec<_>>();
-
-    file_names.sort();
-
-    for file_name in file_names {
-        let mut new_name = file_name.clone();
-        new_name.remove(0);
-        vec.push(Renderable::from_disk_image(
-            &(dir.to_string() + &new_name),
-            RenderableType::MainImage,
-        ));
-        fs::rename(
-            &(dir.to_string() + file_name),
-            &(dir.to_string() + &new_name),
-        )
-        .expect("Error renaming file");
+        .take(index)
+        .filter(|r| paths.contains(&r.img))
+        .count();
+
+    for vec in vecs.iter_mut() {
+        vec.retain(|r| !paths.contains(&r.img));
+    }
+
+    if index - removed_before < vecs[zoom].len() {
+        index - removed_before
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use timecode::Timecode;
+
+    fn frame(lc_code: &str, time: &str) -> Frame {
+        Frame {
+            lc_code: lc_code.to_string(),
+            time: time.parse().unwrap(),
+            path: format!("img/{0}/{0}.T.{1}.png", lc_code, time),
+        }
+    }
+
+    fn renderables(frames: &[Frame]) -> Vec<Vec<Renderable>> {
+        vec![
+            frames.iter().map(Renderable::from_frame).collect(),
+            Vec::new(),
+        ]
+    }
+
+    fn times(vec: &[Renderable]) -> Vec<Timecode> {
+        vec.iter()
+            .map(|r| Timecode::from_file_name(r.img.rsplit('/').next().unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn new_frames_are_added_in_order_without_moving_the_current_frame() {
+        let site = RadarSite::new("04", "Newcastle", &[3, 4]);
+        let old = frame("IDR043", "201901010000");
+        let new = frame("IDR043", "201901010010");
+        let mut vecs = renderables(&[old.clone(), new.clone()]);
+
+        // Showing the newest frame, an older one arrives late
+        let index = add_new_renderable(&mut vecs, &frame("IDR043", "201901010005"), &site, 0, 1);
+        assert_eq!(index, 2);
+        assert_eq!(
+            times(&vecs[0]),
+            vec![old.time, "201901010005".parse().unwrap(), new.time]
+        );
+
+        // Duplicates and frames at other zoom levels don't move it
+        assert_eq!(add_new_renderable(&mut vecs, &old, &site, 0, 2), 2);
+        assert_eq!(
+            add_new_renderable(&mut vecs, &frame("IDR044", "201901010000"), &site, 0, 2),
+            2
+        );
+        assert_eq!(vecs[0].len(), 3);
+        assert_eq!(vecs[1].len(), 1);
+    }
+
+    #[test]
+    fn speed_changes_in_steps_within_limits() {
+        let speeds = SpeedConfig {
+            step_fps: 2.0,
+            min_fps: 1.0,
+            max_fps: 12.0,
+            ..SpeedConfig::default()
+        };
+        assert_eq!(change_speed(10.0, true, &speeds), 12.0);
+        assert_eq!(change_speed(12.0, true, &speeds), 12.0);
+        assert_eq!(change_speed(10.0, false, &speeds), 8.0);
+        assert_eq!(change_speed(2.0, false, &speeds), 1.0);
+        assert_eq!(frame_duration(4.0), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn fade_happens_just_before_the_next_frame() {
+        let now = Instant::now();
+        let fade = Duration::from_millis(100);
+        let at = |ms| now + Duration::from_millis(ms);
+
+        assert_eq!(fade_amount(now, at(200), fade), 0.0);
+        assert_eq!(fade_amount(now, at(100), fade), 0.0);
+        assert!((fade_amount(now, at(25), fade) - 0.75).abs() < 0.001);
+        assert_eq!(fade_amount(now, now, fade), 1.0);
+        assert_eq!(fade_amount(now, at(25), Duration::from_millis(0)), 0.0);
+
+        // Sleep until the fade starts, then wake often until the frame
+        assert_eq!(wake_time(now, at(200), fade, false), at(100));
+        assert_eq!(wake_time(now, at(50), fade, false), now + FADE_STEP);
+        assert_eq!(wake_time(now, at(5), fade, false), at(5));
+        assert_eq!(wake_time(now, at(50), fade, true), at(50));
+    }
+
+    #[test]
+    fn pruned_frames_are_removed_without_moving_the_current_frame() {
+        let frames = [
+            frame("IDR043", "201901010000"),
+            frame("IDR043", "201901010005"),
+            frame("IDR043", "201901010010"),
+        ];
+        let mut vecs = renderables(&frames);
+
+        let index = remove_renderables(&mut vecs, &[frames[0].path.clone()], 0, 2);
+        assert_eq!(index, 1);
+        assert_eq!(vecs[0][index].img, frames[2].path);
+
+        // Removing the current frame restarts the loop
+        let index = remove_renderables(&mut vecs, &[frames[2].path.clone()], 0, 1);
+        assert_eq!(index, 0);
     }
 }
@@ -0,0 +1,232 @@
+// Draws the same layers as the window's Renderer into an image in memory, so frames can be
+// rendered without a display (e.g. from a cron job) and the compositing can be tested.
+// Slower than the GPU but only a handful of images are drawn per frame.
+
+use image::imageops;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use image::Pixel;
+use image::Rgba;
+use image::RgbaImage;
+
+use error::Error;
+use error::Result;
+use image_viewer::decoder;
+use image_viewer::font;
+use image_viewer::layout::Layout;
+use image_viewer::layout::Rect;
+use image_viewer::overlay::TextTarget;
+use image_viewer::renderable::Renderable;
+use image_viewer::renderable::RenderableType;
+use image_viewer::texture_cache::TextureCache;
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const SHADOW: Rgba<u8> = Rgba([0, 0, 0, 204]);
+
+pub struct Compositor {
+    layout: Layout,
+    canvas: RgbaImage,
+    images: TextureCache<RgbaImage>, // Decoded images at their own size
+}
+
+impl Compositor {
+    // Renders 'size' pixels, keeping up to 'cache_bytes' of decoded images (0 for no limit)
+    pub fn new(size: (u32, u32), cache_bytes: usize) -> Self {
+        Compositor {
+            layout: Layout::new(size),
+            canvas: RgbaImage::from_pixel(size.0, size.1, BLACK),
+            images: TextureCache::new(cache_bytes),
+        }
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    // Clear the canvas to start drawing the next frame
+    pub fn new_frame(&mut self) {
+        for pixel in self.canvas.pixels_mut() {
+            *pixel = BLACK;
+        }
+    }
+
+    pub fn draw(&mut self, item: &Renderable) -> Result<()> {
+        let rect = self.layout.rect(item.kind);
+        self.draw_image(&item.img, rect)
+    }
+
+    // Same as Renderer::draw_progress_bar, the whole image squashed into the first 'progress' of
+    // its place
+    pub fn draw_progress_bar(&mut self, item: &Renderable, progress: f32) -> Result<()> {
+        let mut rect = self.layout.rect(item.kind);
+        rect.width *= progress.clamp(0.0, 1.0);
+        self.draw_image(&item.img, rect)
+    }
+
+    // Size in pixels of 'radar_image'
+    pub fn radar_size(&self) -> (u32, u32) {
+        let (_, _, width, height) = pixels(self.layout.rect(RenderableType::MainImage));
+        (width, height)
+    }
+
+    // Copy of the radar image part of the frame, without the UI below it
+    pub fn radar_image(&self) -> RgbaImage {
+        let (x, y, width, height) = pixels(self.layout.rect(RenderableType::MainImage));
+        imageops::crop_imm(&self.canvas, x, y, width, height).to_image()
+    }
+
+    // Save as an opaque image, the canvas starts black so there's nothing to see through to
+    pub fn save(&self, path: &str) -> Result<()> {
+        DynamicImage::ImageRgba8(self.canvas.clone())
+            .to_rgb8()
+            .save(path)
+            .map_err(|e| Error::Render(format!("Failed to save {}: {}", path, e)))
+    }
+
+    // Draw the image at 'path' stretched over 'rect', blended over what is already there
+    fn draw_image(&mut self, path: &str, rect: Rect) -> Result<()> {
+        let (x, y, width, height) = pixels(rect);
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        if !self.images.contains(path) {
+            let decoded = decoder::decode(path)?;
+            let (w, h) = decoded.dimensions;
+            let image = RgbaImage::from_raw(w, h, decoded.pixels)
+                .ok_or_else(|| Error::Decode(format!("{}: wrong number of pixels", path)))?;
+            let bytes = image.len();
+            self.images.insert(path, image, bytes);
+        }
+        let image = match self.images.get(path) {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+
+        if image.dimensions() == (width, height) {
+            imageops::overlay(&mut self.canvas, image, x as i64, y as i64);
+        } else {
+            let scaled = imageops::resize(image, width, height, FilterType::Triangle);
+            imageops::overlay(&mut self.canvas, &scaled, x as i64, y as i64);
+        }
+        Ok(())
+    }
+
+    fn draw_glyphs(&mut self, text: &str, (x, y): (f32, f32), scale: f32, colour: Rgba<u8>) {
+        let scale = scale.max(1.0) as u32;
+        let (x, y) = (x.round() as u32, y.round() as u32);
+        let (width, height) = self.canvas.dimensions();
+
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as u32 * font::GLYPH_ADVANCE * scale;
+            for gy in 0..font::GLYPH_HEIGHT {
+                for gx in 0..font::GLYPH_WIDTH {
+                    if !font::glyph_pixel(c, gx, gy) {
+                        continue;
+                    }
+                    for py in y + gy * scale..y + (gy + 1) * scale {
+                        for px in left + gx * scale..left + (gx + 1) * scale {
+                            if px < width && py < height {
+                                self.canvas.get_pixel_mut(px, py).blend(&colour);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 'rect' rounded to whole pixels as (x, y, width, height)
+fn pixels(rect: Rect) -> (u32, u32, u32, u32) {
+    let x = rect.x.round() as u32;
+    let y = rect.y.round() as u32;
+    let width = (rect.x + rect.width).round() as u32 - x;
+    let height = (rect.y + rect.height).round() as u32 - y;
+    (x, y, width, height)
+}
+
+impl TextTarget for Compositor {
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, scale: f32) -> Result<()> {
+        self.draw_glyphs(text, (x + scale, y + scale), scale, SHADOW);
+        self.draw_glyphs(text, (x, y), scale, WHITE);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use temp_dir::TempDir;
+
+    // Save a 'size' pixel square image of 'colour' to the test folder
+    fn solid(dir: &str, name: &str, size: u32, colour: [u8; 4]) -> Renderable {
+        let path = format!("{}/{}", dir, name);
+        RgbaImage::from_pixel(size, size, Rgba(colour))
+            .save(&path)
+            .unwrap();
+        Renderable::from_disk_image(&path, RenderableType::MainImage)
+    }
+
+    #[test]
+    fn layers_are_blended_in_order() {
+        let temp = TempDir::new("compositor");
+        let dir = temp.path();
+
+        let background = solid(dir, "background.png", 4, [0, 0, 255, 255]);
+        let clear = solid(dir, "clear.png", 4, [255, 0, 0, 0]);
+        let half = solid(dir, "half.png", 2, [255, 0, 0, 128]);
+        let mut bar = solid(dir, "bar.png", 4, [0, 255, 0, 255]);
+        bar.kind = RenderableType::UpperUI;
+
+        // 10x10 radar image above a 10x2 band for the bars
+        let mut compositor = Compositor::new((10, 12), 0);
+        compositor.draw(&background).unwrap();
+        compositor.draw(&clear).unwrap();
+        compositor.draw(&half).unwrap();
+        compositor.draw_progress_bar(&bar, 0.5).unwrap();
+
+        let image = &compositor.canvas;
+        let pixel = image.get_pixel(5, 5).0;
+        assert!(pixel[0] > 120 && pixel[0] < 136, "{:?}", pixel);
+        assert!(pixel[2] > 120 && pixel[2] < 136, "{:?}", pixel);
+
+        // The bar fills the left half of its row, and the rest stays clear
+        assert_eq!(image.get_pixel(2, 10).0, [0, 255, 0, 255]);
+        assert_eq!(image.get_pixel(7, 10).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(7, 11).0, [0, 0, 0, 255]);
+
+        let radar = compositor.radar_image();
+        assert_eq!(radar.dimensions(), (10, 10));
+        assert_eq!(compositor.radar_size(), (10, 10));
+        assert_eq!(radar.get_pixel(5, 5), compositor.canvas.get_pixel(5, 5));
+
+        compositor.new_frame();
+        assert_eq!(compositor.canvas.get_pixel(5, 5).0, [0, 0, 0, 255]);
+
+        // Missing images are an error rather than a blank layer
+        let missing = Renderable::from_disk_image("missing.png", RenderableType::MainImage);
+        assert!(compositor.draw(&missing).is_err());
+    }
+
+    #[test]
+    fn text_is_drawn_with_a_shadow() {
+        let mut compositor = Compositor::new((40, 40), 0);
+        let grey = Rgba([128, 128, 128, 255]);
+        for pixel in compositor.canvas.pixels_mut() {
+            *pixel = grey;
+        }
+        compositor.draw_text("1", 0.0, 0.0, 2.0).unwrap();
+        let image = &compositor.canvas;
+
+        // The top of '1' is its middle pixel, 2x2 screen pixels at scale 2
+        assert_eq!(image.get_pixel(4, 0).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(5, 1).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(0, 0), &grey);
+        // The bottom row is 3 glyph pixels wide, with the shadow below and to the right of it
+        assert_eq!(image.get_pixel(2, 12).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(2, 14), &grey);
+        assert!(image.get_pixel(8, 14).0[0] < 64);
+    }
+}
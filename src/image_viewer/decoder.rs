@@ -0,0 +1,158 @@
+// Reading images from disk into RGBA pixels. In the viewer this happens on a pool of worker
+// threads, so the render thread only has to upload the pixels to the GPU.
+
+use std::collections::HashSet;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+use error::decode_error;
+use error::Result;
+
+// RGBA pixels of an image, top row first
+pub struct DecodedImage {
+    pub pixels: Vec<u8>,
+    pub dimensions: (u32, u32),
+}
+
+// Read and decode the image at 'path'
+pub fn decode(path: &str) -> Result<DecodedImage> {
+    let image = image::open(path)
+        .map_err(|e| decode_error(path, e))?
+        .to_rgba8();
+
+    Ok(DecodedImage {
+        dimensions: image.dimensions(),
+        pixels: image.into_raw(),
+    })
+}
+
+// Decodes images on a pool of worker threads. Each requested path is decoded once and handed back
+// by 'finished'. Paths that fail aren't requested again until 'retry' is called
+pub struct Decoder {
+    requests: Sender<String>,
+    results: Receiver<(String, Result<DecodedImage>)>,
+    pending: HashSet<String>, // Requested but not handed back yet
+    failed: HashSet<String>,
+}
+
+impl Decoder {
+    pub fn new(workers: usize) -> Self {
+        let (requests, request_receiver) = channel::<String>();
+        let (result_sender, results) = channel();
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+
+        for _ in 0..workers.max(1) {
+            let request_receiver = request_receiver.clone();
+            let result_sender = result_sender.clone();
+
+            // Each worker stops when the Decoder is dropped and the request channel closes
+            thread::spawn(move || loop {
+                // Only hold the lock while waiting, so the others can take requests while this
+                // one decodes
+                let request = match request_receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => break,
+                };
+                let path = match request {
+                    Ok(path) => path,
+                    Err(_) => break,
+                };
+
+                let result = decode(&path);
+                if result_sender.send((path, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Decoder {
+            requests,
+            results,
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    // Ask for 'path' to be decoded, unless it already has been or has failed
+    pub fn request(&mut self, path: &str) {
+        if self.failed.contains(path) {
+            return;
+        }
+        if self.pending.insert(path.to_string()) {
+            // If every worker has died there's nothing to do but leave the image undrawn
+            if self.requests.send(path.to_string()).is_err() {
+                self.pending.remove(path);
+            }
+        }
+    }
+
+    // Allow a path that failed to be requested again, e.g. because the file has been replaced
+    pub fn retry(&mut self, path: &str) {
+        self.failed.remove(path);
+    }
+
+    // Images that have finished decoding since the last call
+    pub fn finished(&mut self) -> Vec<(String, Result<DecodedImage>)> {
+        let finished: Vec<_> = self.results.try_iter().collect();
+        for (path, result) in &finished {
+            self.pending.remove(path);
+            if result.is_err() {
+                self.failed.insert(path.clone());
+            }
+        }
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::mock::png;
+    use std::fs;
+    use std::time::Duration;
+    use std::time::Instant;
+    use temp_dir::TempDir;
+
+    #[test]
+    fn decodes_in_the_background() {
+        let temp = TempDir::new("decoder");
+        let good = temp.join("good.png");
+        let bad = temp.join("bad.png");
+        fs::write(&good, png()).unwrap();
+        fs::write(&bad, b"not a png").unwrap();
+
+        let mut decoder = Decoder::new(2);
+        decoder.request(&good);
+        decoder.request(&bad);
+        decoder.request(&good);
+        assert!(decoder.pending.contains(&good));
+
+        let mut finished = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while finished.len() < 2 && Instant::now() < deadline {
+            finished.extend(decoder.finished());
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // The workers can finish in any order
+        assert_eq!(finished.len(), 2);
+        finished.sort_by(|a, b| b.0.cmp(&a.0));
+        let image = finished[0].1.as_ref().unwrap();
+        assert_eq!(finished[0].0, good);
+        assert_eq!(image.dimensions, (2, 2));
+        assert_eq!(image.pixels.len(), 2 * 2 * 4);
+        assert!(finished[1].1.is_err());
+        assert!(!decoder.pending.contains(&good));
+
+        // Failures aren't retried until asked
+        decoder.request(&bad);
+        assert!(!decoder.pending.contains(&bad));
+        decoder.retry(&bad);
+        decoder.request(&bad);
+        assert!(decoder.pending.contains(&bad));
+    }
+}
@@ -0,0 +1,131 @@
+// Writing a loop of composited frames to an animated image, for sharing outside the viewer.
+// The format is picked from the file extension: .gif, or .png / .apng for an animated PNG.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+
+use image::codecs::gif::GifEncoder;
+use image::codecs::gif::Repeat;
+use image::Delay;
+use image::RgbaImage;
+
+use error::Error;
+use error::Result;
+
+// Speed of GIF colour quantisation, 1 is best quality and 30 is fastest
+const GIF_SPEED: i32 = 10;
+
+pub enum AnimationWriter {
+    Gif(GifEncoder<File>),
+    Apng(png::Writer<BufWriter<File>>),
+}
+
+impl AnimationWriter {
+    // Start an animation of 'frames' frames of 'size' pixels at 'path', looping forever
+    pub fn create(path: &str, (width, height): (u32, u32), frames: usize) -> Result<Self> {
+        let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+
+        match extension.as_str() {
+            "gif" => {
+                let mut encoder = GifEncoder::new_with_speed(File::create(path)?, GIF_SPEED);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|e| export_error(path, e))?;
+                Ok(AnimationWriter::Gif(encoder))
+            }
+            "png" | "apng" => {
+                let file = BufWriter::new(File::create(path)?);
+                let mut encoder = png::Encoder::new(file, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .set_animated(frames as u32, 0)
+                    .map_err(|e| export_error(path, e))?;
+                let writer = encoder.write_header().map_err(|e| export_error(path, e))?;
+                Ok(AnimationWriter::Apng(writer))
+            }
+            _ => Err(Error::Parse(format!(
+                "Can't export to {}, use a .gif or .png file name",
+                path
+            ))),
+        }
+    }
+
+    // Add the next frame, shown for 'delay'
+    pub fn add_frame(&mut self, image: RgbaImage, delay: Duration) -> Result<()> {
+        match self {
+            AnimationWriter::Gif(encoder) => {
+                let delay = Delay::from_saturating_duration(delay);
+                encoder
+                    .encode_frame(image::Frame::from_parts(image, 0, 0, delay))
+                    .map_err(|e| export_error("GIF frame", e))
+            }
+            AnimationWriter::Apng(writer) => {
+                let millis = delay.as_millis().min(u16::MAX as u128) as u16;
+                writer
+                    .set_frame_delay(millis, 1000)
+                    .and_then(|_| writer.write_image_data(&image))
+                    .map_err(|e| export_error("PNG frame", e))
+            }
+        }
+    }
+
+    // Write out the end of the file. Every frame promised to 'create' must have been added
+    pub fn finish(self) -> Result<()> {
+        match self {
+            AnimationWriter::Gif(encoder) => {
+                // The trailer is written when the encoder is dropped
+                drop(encoder);
+                Ok(())
+            }
+            AnimationWriter::Apng(writer) => writer.finish().map_err(|e| export_error("PNG", e)),
+        }
+    }
+}
+
+fn export_error<E: std::fmt::Display>(what: &str, e: E) -> Error {
+    Error::Render(format!("Failed to write {}: {}", what, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+    use image::Rgba;
+    use std::io::BufReader;
+    use temp_dir::TempDir;
+
+    fn export(path: &str) {
+        let mut writer = AnimationWriter::create(path, (4, 4), 2).unwrap();
+        for colour in &[[255, 0, 0, 255], [0, 0, 255, 255]] {
+            let image = RgbaImage::from_pixel(4, 4, Rgba(*colour));
+            writer.add_frame(image, Duration::from_millis(200)).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn exports_gif_and_apng() {
+        let temp = TempDir::new("export");
+
+        let gif = temp.join("loop.gif");
+        export(&gif);
+        let file = BufReader::new(File::open(&gif).unwrap());
+        let decoder = image::codecs::gif::GifDecoder::new(file).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].delay(), Delay::from_numer_denom_ms(200, 1));
+        assert_eq!(frames[1].buffer().get_pixel(0, 0).0, [0, 0, 255, 255]);
+
+        let apng = temp.join("loop.png");
+        export(&apng);
+        let decoder = png::Decoder::new(File::open(&apng).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control().unwrap();
+        assert_eq!(control.num_frames, 2);
+        assert_eq!(control.num_plays, 0);
+
+        assert!(AnimationWriter::create("loop.mp4", (4, 4), 2).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+// Built in 5x7 bitmap font for the on-screen overlay.
+// Only upper case glyphs are included, lower case letters are drawn as upper case and anything
+// else without a glyph is drawn as '?'.
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+// Distance from the start of one character to the next
+pub const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+// Each glyph sits in a cell with a transparent border so neighbours never bleed into each other
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 2;
+const CELL_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+// Rows from top to bottom, the highest of the 5 bits is the leftmost pixel
+const GLYPHS: &[(char, [u8; 7])] = &[
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    ('A', [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('"', [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('&', [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D]),
+    ('\'', [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('*', [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    ('<', [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('>', [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08]),
+    ('[', [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E]),
+    (']', [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    ('|', [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
+
+// Position of the glyph for 'c' in the atlas
+fn glyph_index(c: char) -> usize {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .position(|&(glyph, _)| glyph == c)
+        .unwrap_or(1)
+}
+
+// True if the pixel 'x' across and 'y' down from the top left of the glyph for 'c' is set
+pub fn glyph_pixel(c: char, x: u32, y: u32) -> bool {
+    let rows = GLYPHS[glyph_index(c)].1;
+    x < GLYPH_WIDTH && y < GLYPH_HEIGHT && rows[y as usize] & (0x10 >> x) != 0
+}
+
+// Number of characters of text at 'scale' that fit across 'width' pixels
+pub fn columns(width: f32, scale: f32) -> usize {
+    (width / (GLYPH_ADVANCE as f32 * scale)) as usize
+}
+
+// Height in pixels of a line of text at 'scale'
+pub fn line_height(scale: f32) -> f32 {
+    GLYPH_HEIGHT as f32 * scale
+}
+
+// RGBA pixels of every glyph side by side in a single row, top row first, white on transparent.
+// Returns the pixels and their dimensions
+pub fn atlas() -> (Vec<u8>, (u32, u32)) {
+    let width = CELL_WIDTH * GLYPHS.len() as u32;
+    let mut pixels = vec![0; (width * CELL_HEIGHT * 4) as usize];
+
+    for (i, (_, rows)) in GLYPHS.iter().enumerate() {
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                if row & (0x10 >> x) != 0 {
+                    let px = i as u32 * CELL_WIDTH + 1 + x;
+                    let py = 1 + y as u32;
+                    let offset = ((py * width + px) * 4) as usize;
+                    pixels[offset..offset + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+
+    (pixels, (width, CELL_HEIGHT))
+}
+
+// Texture coordinates of the glyph for 'c' as (left, bottom, width, height), with the atlas
+// uploaded bottom row first as OpenGL expects
+pub fn glyph_rect(c: char) -> [f32; 4] {
+    let atlas_width = (CELL_WIDTH * GLYPHS.len() as u32) as f32;
+    let left = (glyph_index(c) as u32 * CELL_WIDTH + 1) as f32 / atlas_width;
+    [
+        left,
+        1.0 / CELL_HEIGHT as f32,
+        GLYPH_WIDTH as f32 / atlas_width,
+        GLYPH_HEIGHT as f32 / CELL_HEIGHT as f32,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atlas_contains_glyph_pixels() {
+        let (pixels, (width, height)) = atlas();
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        // The top row of '1' is a single pixel in the middle of its cell
+        let cell = glyph_index('1') as u32 * CELL_WIDTH;
+        let alpha = |x: u32, y: u32| pixels[((y * width + cell + x) * 4 + 3) as usize];
+        assert_eq!(alpha(3, 1), 255);
+        assert_eq!(alpha(2, 1), 0);
+        assert_eq!(alpha(0, 0), 0);
+    }
+
+    #[test]
+    fn lower_case_and_unknown_characters() {
+        assert_eq!(glyph_index('a'), glyph_index('A'));
+        assert_eq!(glyph_index('~'), glyph_index('?'));
+        assert_ne!(glyph_index(' '), glyph_index('?'));
+    }
+}
@@ -0,0 +1,147 @@
+// Where everything goes in the window, worked out from its size so it can be resized freely.
+// The bottom of the window is a band holding the two progress bars, and the radar image is the
+// biggest square that fits above it, centred.
+
+use image_viewer::renderable::RenderableType;
+
+// Size the window opens at, and of headless renders: a 512px radar image with 128px of UI below
+pub const DEFAULT_SIZE: (u32, u32) = (512, 512 + 128);
+
+// Fraction of the window height given to the progress bars, 128px of the original 640px window
+const UI_BAND: f32 = 0.2;
+
+// A rectangle in pixels from the top left of the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+pub struct Layout {
+    width: f32,
+    height: f32,
+}
+
+impl Layout {
+    pub fn new((width, height): (u32, u32)) -> Self {
+        Layout {
+            width: width.max(1) as f32,
+            height: height.max(1) as f32,
+        }
+    }
+
+    pub fn rect(&self, kind: RenderableType) -> Rect {
+        let band = self.height * UI_BAND;
+        let above_band = self.height - band;
+
+        match kind {
+            RenderableType::MainImage => {
+                let size = self.width.min(above_band);
+                Rect {
+                    x: (self.width - size) / 2.0,
+                    y: (above_band - size) / 2.0,
+                    width: size,
+                    height: size,
+                }
+            }
+            RenderableType::UpperUI => Rect {
+                x: 0.0,
+                y: above_band,
+                width: self.width,
+                height: band / 2.0,
+            },
+            RenderableType::BottomUI => Rect {
+                x: 0.0,
+                y: above_band + band / 2.0,
+                width: self.width,
+                height: band / 2.0,
+            },
+        }
+    }
+
+    // Transformation from the -1 -> 1 square to where 'kind' goes in the window
+    pub fn matrix(&self, kind: RenderableType) -> [[f32; 4]; 4] {
+        let rect = self.rect(kind);
+        let (sx, sy) = (rect.width / self.width, rect.height / self.height);
+        let tx = (rect.x + rect.width / 2.0) * 2.0 / self.width - 1.0;
+        let ty = 1.0 - (rect.y + rect.height / 2.0) * 2.0 / self.height;
+
+        [
+            [sx, 0.0, 0.0, 0.0],
+            [0.0, sy, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [tx, ty, 0.0, 1.0],
+        ]
+    }
+
+    // True if the row 'y' pixels from the top of the window passes through 'kind'
+    pub fn contains_row(&self, kind: RenderableType, y: f32) -> bool {
+        let rect = self.rect(kind);
+        y >= rect.y && y <= rect.y + rect.height
+    }
+
+    // Fraction of the way across the window 'x' pixels from the left is
+    pub fn fraction_across(&self, x: f32) -> f32 {
+        x / self.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn original_window_size_matches_the_old_layout() {
+        let layout = Layout::new((512, 640));
+        let radar = layout.rect(RenderableType::MainImage);
+        assert_eq!(
+            radar,
+            Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 512.0,
+                height: 512.0
+            }
+        );
+
+        // The old hard coded matrices
+        let matrix = layout.matrix(RenderableType::MainImage);
+        assert_eq!((matrix[0][0], matrix[1][1]), (1.0, 0.8));
+        assert!((matrix[3][1] - 0.2).abs() < 0.0001);
+        let matrix = layout.matrix(RenderableType::UpperUI);
+        assert!((matrix[1][1] - 0.1).abs() < 0.0001);
+        assert!((matrix[3][1] + 0.7).abs() < 0.0001);
+        let matrix = layout.matrix(RenderableType::BottomUI);
+        assert!((matrix[3][1] + 0.9).abs() < 0.0001);
+
+        assert!(layout.contains_row(RenderableType::UpperUI, 540.0));
+        assert!(!layout.contains_row(RenderableType::UpperUI, 600.0));
+        assert!(layout.contains_row(RenderableType::BottomUI, 600.0));
+    }
+
+    #[test]
+    fn radar_stays_square_and_centred() {
+        // Wide: limited by the height above the band
+        let radar = Layout::new((1920, 1080)).rect(RenderableType::MainImage);
+        assert_eq!(radar.width, radar.height);
+        assert_eq!(radar.height, 864.0);
+        assert_eq!(radar.x, (1920.0 - 864.0) / 2.0);
+        assert_eq!(radar.y, 0.0);
+
+        // Tall: limited by the width
+        let layout = Layout::new((400, 1000));
+        let radar = layout.rect(RenderableType::MainImage);
+        assert_eq!(
+            radar,
+            Rect {
+                x: 0.0,
+                y: 200.0,
+                width: 400.0,
+                height: 400.0
+            }
+        );
+        assert_eq!(layout.rect(RenderableType::UpperUI).height, 100.0);
+    }
+}
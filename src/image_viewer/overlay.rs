@@ -0,0 +1,160 @@
+// Text drawn over the radar image: what is being shown at the top, the downloader's status at the
+// bottom.
+
+use chrono::Local;
+
+use error::Result;
+use image_viewer::font;
+use image_viewer::layout::Rect;
+use image_viewer::playback::describe_window;
+use radar::RadarSite;
+use timecode::Timecode;
+
+// Size of the radar image each font pixel is scaled up for, e.g. 2x on the original 512px image
+const PIXELS_PER_SCALE: f32 = 256.0;
+// Gap between the text and the edge of the radar image, and between lines, in screen pixels
+const MARGIN: f32 = 6.0;
+
+// Something the overlay can be drawn on, the window or a headless render
+pub trait TextTarget {
+    // Draw 'text' with its top left corner 'x', 'y' pixels from the top left of the window, with
+    // each font pixel 'scale' pixels square
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, scale: f32) -> Result<()>;
+}
+
+// Everything the overlay describes
+pub struct OverlayInfo<'a> {
+    pub site: &'a RadarSite,
+    pub zoom: usize,
+    pub frame_time: Option<Timecode>, // Time of the frame on screen, if there is one
+    pub index: usize,                 // Position of the frame in the loop
+    pub count: usize,                 // Frames in the loop
+    pub window_hours: f32,            // Length of the loop, 0 for all frames
+    pub fps: f32,                     // Playback speed
+    pub paused: bool,
+    pub status: String, // Last download status
+}
+
+// Lines of text for the top of the radar image
+pub fn top_lines(info: &OverlayInfo) -> Vec<String> {
+    let range = &info.site.ranges[info.zoom];
+    let time = describe_time(info.frame_time);
+    let position = if info.count > 0 {
+        format!("Frame {}/{}", info.index + 1, info.count)
+    } else {
+        "Frame -/-".to_string()
+    };
+
+    let speed = if info.paused {
+        "Paused".to_string()
+    } else {
+        format!("{}fps", info.fps)
+    };
+
+    vec![
+        time,
+        format!("{} {}km", info.site.name, range.km),
+        format!(
+            "{}  {}  {}",
+            position,
+            speed,
+            describe_window(info.window_hours)
+        ),
+    ]
+}
+
+// Local time of a frame, e.g. "Thu 31 Jan 23:55"
+pub fn describe_time(time: Option<Timecode>) -> String {
+    match time {
+        Some(time) => time
+            .datetime()
+            .with_timezone(&Local)
+            .format("%a %d %b %H:%M")
+            .to_string(),
+        None => "No frames yet".to_string(),
+    }
+}
+
+// Draw the overlay inside the radar image at 'radar'. The text grows with the image
+pub fn draw<T: TextTarget>(target: &mut T, info: &OverlayInfo, radar: Rect) -> Result<()> {
+    let scale = text_scale(radar);
+    let columns = font::columns(radar.width, scale).saturating_sub(2);
+    let line_height = font::line_height(scale) + MARGIN;
+    let x = radar.x + MARGIN;
+
+    for (i, line) in top_lines(info).iter().enumerate() {
+        let y = radar.y + MARGIN + i as f32 * line_height;
+        target.draw_text(&truncate(line, columns), x, y, scale)?;
+    }
+
+    let y = radar.y + radar.height - line_height;
+    target.draw_text(&truncate(&info.status, columns), x, y, scale)
+}
+
+// Draw just the time of the frame in the corner of 'radar', for exported animations
+pub fn draw_time<T: TextTarget>(target: &mut T, time: Option<Timecode>, radar: Rect) -> Result<()> {
+    let scale = text_scale(radar);
+    target.draw_text(
+        &describe_time(time),
+        radar.x + MARGIN,
+        radar.y + MARGIN,
+        scale,
+    )
+}
+
+// Size of each font pixel on the radar image at 'radar'
+fn text_scale(radar: Rect) -> f32 {
+    (radar.width / PIXELS_PER_SCALE).round().max(1.0)
+}
+
+// The first 'columns' characters of 'text'
+fn truncate(text: &str, columns: usize) -> String {
+    text.chars().take(columns).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_lines_describe_the_frame() {
+        let site = RadarSite::new("04", "Newcastle", &[2, 3, 4]);
+        let time: Timecode = "201901312355".parse().unwrap();
+        let mut info = OverlayInfo {
+            site: &site,
+            zoom: 1,
+            frame_time: Some(time),
+            index: 4,
+            count: 12,
+            window_hours: 3.0,
+            fps: 10.0,
+            paused: false,
+            status: "Waiting".to_string(),
+        };
+
+        let local = time.datetime().with_timezone(&Local);
+        assert_eq!(
+            top_lines(&info),
+            vec![
+                local.format("%a %d %b %H:%M").to_string(),
+                "Newcastle 128km".to_string(),
+                "Frame 5/12  10fps  Last 3h".to_string(),
+            ]
+        );
+
+        info.frame_time = None;
+        info.window_hours = 0.0;
+        info.count = 0;
+        assert_eq!(top_lines(&info)[0], "No frames yet");
+        assert_eq!(top_lines(&info)[2], "Frame -/-  10fps  All frames");
+
+        info.paused = true;
+        assert_eq!(top_lines(&info)[2], "Frame -/-  Paused  All frames");
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        assert_eq!(truncate("Download failed", 8), "Download");
+        assert_eq!(truncate("Waiting", 80), "Waiting");
+    }
+}
@@ -0,0 +1,222 @@
+// Moving around the loop of frames in response to the playback controls.
+// The loop is the range of frames being animated, the most recent part of all the frames loaded.
+
+use chrono::Duration;
+use std::ops::Range;
+
+use config::LoopConfig;
+use image_viewer::renderable::Renderable;
+use timecode::Timecode;
+
+// The frames of 'frames' (in time order) inside a loop window of the newest 'hours' (0 for all),
+// further limited to the newest 'max_frames' (0 for no limit). The window is measured back from
+// the newest frame, so an old archive still has something to play
+pub fn loop_range(frames: &[Renderable], hours: f32, max_frames: usize) -> Range<usize> {
+    let len = frames.len();
+    let mut start = 0;
+
+    if hours > 0.0 {
+        if let Some(newest) = frames.last().and_then(|r| r.time) {
+            let cutoff = newest + -Duration::seconds((hours * 3600.0) as i64);
+            start = frames.partition_point(|r| r.time.is_none_or(|time| time < cutoff));
+        }
+    }
+    if max_frames > 0 {
+        start = start.max(len.saturating_sub(max_frames));
+    }
+
+    start..len
+}
+
+// The frames of 'frames' (in time order) taken between 'from' and 'to' inclusive, either of which
+// can be left open
+pub fn time_range(
+    frames: &[Renderable],
+    from: Option<Timecode>,
+    to: Option<Timecode>,
+) -> Range<usize> {
+    let start = match from {
+        Some(from) => frames.partition_point(|r| r.time.is_none_or(|time| time < from)),
+        None => 0,
+    };
+    let end = match to {
+        Some(to) => frames.partition_point(|r| r.time.is_none_or(|time| time <= to)),
+        None => frames.len(),
+    };
+
+    start..end.max(start)
+}
+
+// Frame after (or before) 'index' in 'frames', wrapping around at the ends
+pub fn step(index: usize, frames: &Range<usize>, forward: bool) -> usize {
+    if frames.is_empty() {
+        frames.start
+    } else if index < frames.start || index >= frames.end {
+        if forward {
+            frames.start
+        } else {
+            newest(frames)
+        }
+    } else if forward {
+        if index + 1 < frames.end {
+            index + 1
+        } else {
+            frames.start
+        }
+    } else if index == frames.start {
+        newest(frames)
+    } else {
+        index - 1
+    }
+}
+
+// Index of the last frame in 'frames'
+pub fn newest(frames: &Range<usize>) -> usize {
+    frames.end.saturating_sub(1).max(frames.start)
+}
+
+// Frame at 'fraction' (0.0 -> 1.0) of the way through 'frames'.
+// Matches the progress bar, which shows position / len
+pub fn scrub(fraction: f32, frames: &Range<usize>) -> usize {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let position = (fraction * frames.len() as f32) as usize;
+    (frames.start + position).min(newest(frames))
+}
+
+// Step the loop window to the next longer or shorter length in 'config.hour_steps', where 0 (all
+// frames) is longer than any step
+pub fn change_window(hours: f32, longer: bool, config: &LoopConfig) -> f32 {
+    let steps = &config.hour_steps;
+    let value = if longer {
+        if hours <= 0.0 {
+            0.0
+        } else {
+            steps
+                .iter()
+                .cloned()
+                .find(|&step| step > hours)
+                .unwrap_or(0.0)
+        }
+    } else if hours <= 0.0 {
+        steps.last().cloned().unwrap_or(0.0)
+    } else {
+        steps
+            .iter()
+            .cloned()
+            .rev()
+            .find(|&step| step < hours)
+            .unwrap_or(hours)
+    };
+    println!("Loop = {}", describe_window(value));
+    value
+}
+
+// e.g. "Last 3h" or "All frames"
+pub fn describe_window(hours: f32) -> String {
+    if hours > 0.0 {
+        format!("Last {}h", hours)
+    } else {
+        "All frames".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_index::Frame;
+
+    fn frames(times: &[&str]) -> Vec<Renderable> {
+        times
+            .iter()
+            .map(|time| {
+                Renderable::from_frame(&Frame {
+                    lc_code: "IDR043".to_string(),
+                    time: time.parse().unwrap(),
+                    path: format!("img/IDR043/IDR043.T.{}.png", time),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn step_wraps_around() {
+        let all = 0..3;
+        assert_eq!(step(0, &all, true), 1);
+        assert_eq!(step(2, &all, true), 0);
+        assert_eq!(step(1, &all, false), 0);
+        assert_eq!(step(0, &all, false), 2);
+        assert_eq!(step(0, &(0..0), true), 0);
+        assert_eq!(step(0, &(0..0), false), 0);
+
+        // Within a window, and from outside it
+        let window = 2..5;
+        assert_eq!(step(4, &window, true), 2);
+        assert_eq!(step(2, &window, false), 4);
+        assert_eq!(step(0, &window, true), 2);
+    }
+
+    #[test]
+    fn scrub_matches_progress_bar() {
+        assert_eq!(scrub(0.0, &(0..10)), 0);
+        assert_eq!(scrub(0.25, &(0..10)), 2);
+        assert_eq!(scrub(0.99, &(0..10)), 9);
+        assert_eq!(scrub(1.0, &(0..10)), 9);
+        assert_eq!(scrub(-0.5, &(0..10)), 0);
+        assert_eq!(scrub(0.5, &(0..0)), 0);
+        assert_eq!(scrub(0.5, &(10..20)), 15);
+        assert_eq!(newest(&(0..10)), 9);
+        assert_eq!(newest(&(0..0)), 0);
+    }
+
+    #[test]
+    fn loop_range_covers_the_newest_frames() {
+        let frames = frames(&[
+            "201901010000",
+            "201901010100",
+            "201901010130",
+            "201901010200",
+            "201901010300",
+        ]);
+
+        assert_eq!(loop_range(&frames, 0.0, 0), 0..5);
+        assert_eq!(loop_range(&frames, 1.0, 0), 3..5);
+        assert_eq!(loop_range(&frames, 2.0, 0), 1..5);
+        assert_eq!(loop_range(&frames, 2.0, 3), 2..5);
+        assert_eq!(loop_range(&frames, 0.0, 10), 0..5);
+        assert_eq!(loop_range(&[], 1.0, 1), 0..0);
+    }
+
+    #[test]
+    fn time_range_is_inclusive() {
+        let frames = frames(&["201901010000", "201901010100", "201901010200"]);
+        let time = |s: &str| Some(s.parse().unwrap());
+
+        assert_eq!(time_range(&frames, None, None), 0..3);
+        assert_eq!(time_range(&frames, time("201901010100"), None), 1..3);
+        assert_eq!(
+            time_range(&frames, time("201901010001"), time("201901010100")),
+            1..2
+        );
+        assert_eq!(time_range(&frames, None, time("201901010159")), 0..2);
+        assert_eq!(time_range(&frames, time("201901020000"), None), 3..3);
+        assert_eq!(
+            time_range(&frames, time("201901010200"), time("201901010000")),
+            2..2
+        );
+    }
+
+    #[test]
+    fn window_steps_through_presets_then_all() {
+        let config = LoopConfig {
+            hour_steps: vec![1.0, 3.0, 12.0],
+            ..LoopConfig::default()
+        };
+        assert_eq!(change_window(1.0, true, &config), 3.0);
+        assert_eq!(change_window(2.0, true, &config), 3.0);
+        assert_eq!(change_window(12.0, true, &config), 0.0);
+        assert_eq!(change_window(0.0, true, &config), 0.0);
+        assert_eq!(change_window(0.0, false, &config), 12.0);
+        assert_eq!(change_window(3.0, false, &config), 1.0);
+        assert_eq!(change_window(1.0, false, &config), 1.0);
+    }
+}
@@ -1,9 +1,22 @@
+use super::decoder;
+use super::decoder::DecodedImage;
+use super::font;
+use super::layout::Layout;
+use super::layout::DEFAULT_SIZE;
+use super::overlay::TextTarget;
 use super::renderable::Renderable;
+use super::texture_cache::TextureCache;
+use config::KioskConfig;
+use error::Error;
+use error::Result;
 use glium::draw_parameters::{Blend, DrawParameters};
+use glium::glutin::dpi::PhysicalSize;
 use glium::glutin::event_loop::EventLoop;
+use glium::glutin::window::Fullscreen;
 use glium::index::PrimitiveType;
+use glium::texture::RawImage2d;
 use glium::texture::Texture2d;
-use glium::uniforms::{EmptyUniforms, UniformsStorage};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, Uniforms};
 use glium::{Display, Frame, IndexBuffer, Program, Surface, VertexBuffer};
 
 #[derive(Copy, Clone)]
@@ -36,30 +49,73 @@ const VERTICES: [Vertex; 4] = [
 
 const INDICES: [u16; 6] = [0, 2, 1, 1, 3, 2];
 
+// Texture rectangle covering the whole texture
+const FULL_TEXTURE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SHADOW: [f32; 4] = [0.0, 0.0, 0.0, 0.8];
+
+// Texture for a draw call, optionally crossfaded into a second texture by 'blend' (0.0 -> 1.0)
+struct Source<'a> {
+    tex: Sampler<'a, Texture2d>,
+    next_tex: Sampler<'a, Texture2d>,
+    blend: f32,
+}
+
+impl<'a> Source<'a> {
+    fn single(tex: Sampler<'a, Texture2d>) -> Self {
+        Source {
+            tex,
+            next_tex: tex,
+            blend: 0.0,
+        }
+    }
+}
+
 pub struct Renderer {
     pub display: Display, // Pub so outsiders can use it to create textures (maybe not a good idea)
     program: Program,
     vb: VertexBuffer<Vertex>,
     ib: IndexBuffer<u16>,
+    font: Texture2d, // Glyph atlas for draw_text
+    textures: TextureCache<Texture2d>,
 
     target: Option<Frame>,
 }
 
 impl Renderer {
-    pub fn new() -> (Self, EventLoop<()>) {
-        let (display, events_loop) = create_display();
-        let program = link_shader(&display);
-        let (vb, ib) = create_buffers(&display);
+    // 'cache_bytes' is the budget for image textures, 0 for no limit
+    pub fn new(cache_bytes: usize, kiosk: &KioskConfig) -> Result<(Self, EventLoop<()>)> {
+        let (display, events_loop) = create_display(kiosk)?;
+        let program = link_shader(&display)?;
+        let (vb, ib) = create_buffers(&display)?;
+        let font = create_font(&display)?;
 
         let renderer = Renderer {
             display,
             program,
             vb,
             ib,
+            font,
+            textures: TextureCache::new(cache_bytes),
             target: None,
         };
 
-        (renderer, events_loop)
+        Ok((renderer, events_loop))
+    }
+
+    // Layout for the window's current size
+    pub fn layout(&self) -> Layout {
+        Layout::new(self.display.get_framebuffer_dimensions())
+    }
+
+    // Must be called when the window changes size
+    pub fn resize(&self, size: PhysicalSize<u32>) {
+        self.display.gl_window().resize(size);
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.display.gl_window().window().set_title(title);
     }
 
     pub fn new_frame(&mut self) {
@@ -68,102 +124,273 @@ impl Renderer {
         self.target = Some(target);
     }
 
-    pub fn draw(&mut self, item: &mut Renderable) {
-        let params = DrawParameters {
-            blend: Blend::alpha_blending(),
-            ..Default::default()
+    pub fn draw(&mut self, item: &Renderable) -> Result<()> {
+        let matrix = self.layout().matrix(item.kind);
+        self.draw_image(&item.img, matrix)
+    }
+
+    // Draw 'item' crossfaded into 'next' by 'blend', from 0.0 (all 'item') to 1.0 (all 'next').
+    // Both must already be loaded
+    pub fn draw_blended(&mut self, item: &Renderable, next: &Renderable, blend: f32) -> Result<()> {
+        // Counts as a use for both
+        if self.textures.get(&item.img).is_none() || self.textures.get(&next.img).is_none() {
+            return Err(Error::Render(format!(
+                "Textures not loaded: {}, {}",
+                item.img, next.img
+            )));
+        }
+
+        let mut target = self.take_target();
+        let source = Source {
+            tex: self
+                .textures
+                .peek(&item.img)
+                .expect("Texture missing right after use")
+                .sampled(),
+            next_tex: self
+                .textures
+                .peek(&next.img)
+                .expect("Texture missing right after use")
+                .sampled(),
+            blend,
         };
+        let matrix = self.layout().matrix(item.kind);
+        let result = self.draw_quad(&mut target, source, matrix, FULL_TEXTURE, WHITE);
+        self.target = Some(target);
+        result
+    }
 
-        if let Some(target) = &mut self.target {
-            let matrix = item.matrix;
-            target
-                .draw(
-                    &self.vb,
-                    &self.ib,
-                    &self.program,
-                    &uniforms(item.get_texture(&self.display), matrix),
-                    &params,
-                )
-                .expect("Error drawing BG");
-        } else {
-            panic!("Drew without a target, probably draw call without a new_frame call");
+    // Make sure the texture for 'item' is loaded, decoding it now if it hasn't been already.
+    // Only for start up, once the window is running images are decoded by the viewer's Decoder
+    pub fn load(&mut self, item: &Renderable) -> Result<()> {
+        if !self.textures.contains(&item.img) {
+            let image = decoder::decode(&item.img)?;
+            self.upload(&item.img, image)?;
         }
+        Ok(())
     }
 
-    pub fn finish_frame(&mut self) {
+    // Create the texture for the image at 'path' from its decoded pixels
+    pub fn upload(&mut self, path: &str, image: DecodedImage) -> Result<()> {
+        let bytes = image.pixels.len();
+        let raw = RawImage2d::from_raw_rgba_reversed(&image.pixels, image.dimensions);
+        let texture =
+            Texture2d::new(&self.display, raw).map_err(|e| Error::Render(e.to_string()))?;
+        self.textures.insert(path, texture, bytes);
+        Ok(())
+    }
+
+    pub fn is_loaded(&self, path: &str) -> bool {
+        self.textures.contains(path)
+    }
+
+    // Drop the texture for the image at 'path' so it's read from disk again next time
+    pub fn forget(&mut self, path: &str) {
+        self.textures.remove(path);
+    }
+
+    pub fn finish_frame(&mut self) -> Result<()> {
         if let Some(target) = self.target.take() {
-            target.finish().expect("Frame Finishing Error");
-            self.target = None;
+            target.finish().map_err(|e| Error::Render(e.to_string()))
         } else {
             panic!("Finished without a target, probably called without a new_frame call");
         }
     }
 
-    pub(crate) fn draw_progress_bar(&mut self, item: &mut Renderable, progress: f32) {
+    pub(crate) fn draw_progress_bar(&mut self, item: &Renderable, progress: f32) -> Result<()> {
+        let scale = progress;
+        let translation = progress - 1.0;
+        let mut matrix = self.layout().matrix(item.kind);
+        matrix[0][0] = scale;
+        matrix[3][0] = translation;
+
+        self.draw_image(&item.img, matrix)
+    }
+
+    fn draw_glyphs(
+        &self,
+        target: &mut Frame,
+        text: &str,
+        (x, y): (f32, f32),
+        scale: f32,
+        colour: [f32; 4],
+    ) -> Result<()> {
+        let (width, height) = self.display.get_framebuffer_dimensions();
+        let (width, height) = (width as f32, height as f32);
+        let glyph_width = font::GLYPH_WIDTH as f32 * scale;
+        let glyph_height = font::GLYPH_HEIGHT as f32 * scale;
+
+        for (i, c) in text.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+
+            // Convert the glyph's centre from pixels to the -1 -> 1 window coordinates
+            let centre_x = x + i as f32 * font::GLYPH_ADVANCE as f32 * scale + glyph_width / 2.0;
+            let centre_y = y + glyph_height / 2.0;
+            let matrix = [
+                [glyph_width / width, 0.0, 0.0, 0.0],
+                [0.0, glyph_height / height, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [
+                    centre_x * 2.0 / width - 1.0,
+                    1.0 - centre_y * 2.0 / height,
+                    0.0,
+                    1.0,
+                ],
+            ];
+
+            // Sharp edges rather than blurry glyphs when scaled up
+            let sampler = self
+                .font
+                .sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest);
+            self.draw_quad(
+                target,
+                Source::single(sampler),
+                matrix,
+                font::glyph_rect(c),
+                colour,
+            )?;
+        }
+        Ok(())
+    }
+
+    // Draw the image at 'path', which must already be loaded
+    fn draw_image(&mut self, path: &str, matrix: [[f32; 4]; 4]) -> Result<()> {
+        // Counts as a use, so textures drawn every frame are never the ones evicted
+        if self.textures.get(path).is_none() {
+            return Err(Error::Render(format!("Texture not loaded: {}", path)));
+        }
+
+        let mut target = self.take_target();
+        let texture = self
+            .textures
+            .peek(path)
+            .expect("Texture missing right after use");
+        let source = Source::single(texture.sampled());
+        let result = self.draw_quad(&mut target, source, matrix, FULL_TEXTURE, WHITE);
+        self.target = Some(target);
+        result
+    }
+
+    // Draw the part of a texture given by 'tex_rect' to the square transformed by 'matrix',
+    // multiplied by 'colour'
+    fn draw_quad(
+        &self,
+        target: &mut Frame,
+        source: Source,
+        matrix: [[f32; 4]; 4],
+        tex_rect: [f32; 4],
+        colour: [f32; 4],
+    ) -> Result<()> {
         let params = DrawParameters {
             blend: Blend::alpha_blending(),
             ..Default::default()
         };
 
-        let scale = progress;
-        let translation = progress - 1.0;
-        if let Some(target) = &mut self.target {
-            let mut matrix = item.matrix;
-            matrix[0][0] = scale;
-            matrix[3][0] = translation;
-            target
-                .draw(
-                    &self.vb,
-                    &self.ib,
-                    &self.program,
-                    &uniforms(item.get_texture(&self.display), matrix),
-                    &params,
-                )
-                .expect("Error drawing BG");
-        } else {
-            panic!("Drew without a target, probably draw call without a new_frame call");
+        target
+            .draw(
+                &self.vb,
+                &self.ib,
+                &self.program,
+                &uniforms(source, matrix, tex_rect, colour),
+                &params,
+            )
+            .map_err(|e| Error::Render(e.to_string()))
+    }
+
+    // The target is taken out while drawing so it can be drawn to while borrowing the textures
+    fn take_target(&mut self) -> Frame {
+        match self.target.take() {
+            Some(target) => target,
+            None => panic!("Drew without a target, probably draw call without a new_frame call"),
         }
     }
 }
 
-// Just a wrapper to be more readable at the draw call. This type signature is horrible...
-fn uniforms(
-    tex: &Texture2d,
+// Just a wrapper to be more readable at the draw call
+fn uniforms<'a>(
+    source: Source<'a>,
     matrix: [[f32; 4]; 4],
-) -> UniformsStorage<[[f32; 4]; 4], UniformsStorage<&Texture2d, EmptyUniforms>> {
+    tex_rect: [f32; 4],
+    colour: [f32; 4],
+) -> impl Uniforms + 'a {
     uniform! {
-        tex: tex,
-        matrix: matrix
+        tex: source.tex,
+        next_tex: source.next_tex,
+        blend: source.blend,
+        matrix: matrix,
+        tex_rect: tex_rect,
+        colour: colour
+    }
+}
+
+impl TextTarget for Renderer {
+    // A drop shadow keeps the text readable over the radar
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, scale: f32) -> Result<()> {
+        let mut target = self.take_target();
+        let result = self
+            .draw_glyphs(&mut target, text, (x + scale, y + scale), scale, SHADOW)
+            .and_then(|_| self.draw_glyphs(&mut target, text, (x, y), scale, WHITE));
+        self.target = Some(target);
+        result
     }
 }
 
-// Open a window and return the display and the associated events loop
-fn create_display() -> (glium::Display, EventLoop<()>) {
+// Open a window and return the display and the associated events loop.
+// In kiosk mode the window is borderless fullscreen on the chosen monitor, without a cursor
+fn create_display(kiosk: &KioskConfig) -> Result<(glium::Display, EventLoop<()>)> {
     let events_loop = EventLoop::new();
 
-    let window = glium::glutin::window::WindowBuilder::new()
-        .with_inner_size(glium::glutin::dpi::PhysicalSize::new(512, 512 + 128)) // 128 pixels for UI at the bottom
+    let mut window = glium::glutin::window::WindowBuilder::new()
+        .with_inner_size(PhysicalSize::new(DEFAULT_SIZE.0, DEFAULT_SIZE.1))
+        .with_min_inner_size(PhysicalSize::new(128, 160))
+        .with_resizable(true)
         .with_title("Radar Monitor");
 
+    if kiosk.enabled {
+        // Fall back to whichever monitor the window would have opened on
+        let monitor = events_loop.available_monitors().nth(kiosk.monitor);
+        if monitor.is_none() {
+            println!("Monitor {} not found, using the current one", kiosk.monitor);
+        }
+        window = window
+            .with_decorations(false)
+            .with_fullscreen(Some(Fullscreen::Borderless(monitor)));
+    }
+
     let context = glium::glutin::ContextBuilder::new();
 
-    let display =
-        glium::Display::new(window, context, &events_loop).expect("Failed to create display");
+    let display = glium::Display::new(window, context, &events_loop)
+        .map_err(|e| Error::Render(format!("Failed to create display: {}", e)))?;
 
-    (display, events_loop)
+    if kiosk.enabled {
+        display.gl_window().window().set_cursor_visible(false);
+    }
+
+    Ok((display, events_loop))
 }
 
-fn link_shader(display: &Display) -> Program {
+fn link_shader(display: &Display) -> Result<Program> {
     const VERT_SHADER: &str = include_str!("../res/shader.vert");
     const FRAG_SHADER: &str = include_str!("../res/shader.frag");
     Program::from_source(display, VERT_SHADER, FRAG_SHADER, None)
-        .expect("Error creating shader program")
+        .map_err(|e| Error::Render(format!("Error creating shader program: {}", e)))
 }
 
-fn create_buffers(display: &Display) -> (VertexBuffer<Vertex>, IndexBuffer<u16>) {
-    let vb = VertexBuffer::new(display, &VERTICES).expect("Error creating vertex buffer");
+fn create_buffers(display: &Display) -> Result<(VertexBuffer<Vertex>, IndexBuffer<u16>)> {
+    let vb = VertexBuffer::new(display, &VERTICES).map_err(|e| Error::Render(e.to_string()))?;
     let ib = IndexBuffer::new(display, PrimitiveType::TrianglesList, &INDICES)
-        .expect("Error creating index buffer");
+        .map_err(|e| Error::Render(e.to_string()))?;
+
+    Ok((vb, ib))
+}
 
-    (vb, ib)
+// Upload the overlay font's glyph atlas
+fn create_font(display: &Display) -> Result<Texture2d> {
+    let (pixels, dimensions) = font::atlas();
+    let image = RawImage2d::from_raw_rgba_reversed(&pixels, dimensions);
+    Texture2d::new(display, image).map_err(|e| Error::Render(e.to_string()))
 }
@@ -0,0 +1,83 @@
+use chrono::DateTime;
+use chrono::Local;
+
+use event::DownloadEvent;
+
+// What the downloader is currently doing, as far as the viewer knows
+enum Activity {
+    Starting,
+    Waiting,
+    Checking,
+    Watching,
+    Downloading(String), // File name
+    Downloaded(String),  // File name
+    Failed(String),      // Error message
+    Unreachable(String), // Error message
+}
+
+// Summary of the download thread built from its events, for showing to the user
+pub struct DownloadStatus {
+    activity: Activity,
+    last_frame: Option<DateTime<Local>>, // When the last new frame arrived
+    timer_progress: f32,                 // Progress through the current wait, 0.0 -> 1.0
+}
+
+impl DownloadStatus {
+    pub fn new() -> Self {
+        DownloadStatus {
+            activity: Activity::Starting,
+            last_frame: None,
+            timer_progress: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, event: &DownloadEvent) {
+        match event {
+            DownloadEvent::Waiting(progress) => {
+                self.timer_progress = *progress;
+                // Keep showing a failure until the next attempt
+                match self.activity {
+                    Activity::Failed(_) | Activity::Unreachable(_) => (),
+                    _ => self.activity = Activity::Waiting,
+                }
+            }
+            DownloadEvent::Checking => self.activity = Activity::Checking,
+            DownloadEvent::Watching => self.activity = Activity::Watching,
+            DownloadEvent::DownloadStarted(name) => {
+                self.activity = Activity::Downloading(name.clone())
+            }
+            DownloadEvent::DownloadFinished(name) => {
+                self.activity = Activity::Downloaded(name.clone())
+            }
+            DownloadEvent::NewFrame(_) => self.last_frame = Some(Local::now()),
+            DownloadEvent::Pruned(_) | DownloadEvent::BackgroundRefreshed(_) => (),
+            DownloadEvent::Error(e) => self.activity = Activity::Failed(e.to_string()),
+            DownloadEvent::ServerUnreachable(e) => {
+                self.activity = Activity::Unreachable(e.to_string())
+            }
+        }
+    }
+
+    pub fn timer_progress(&self) -> f32 {
+        self.timer_progress
+    }
+
+    // One line summary, e.g. "Waiting (last frame 10:42)"
+    pub fn describe(&self) -> String {
+        let activity = match &self.activity {
+            Activity::Starting => "Starting".to_string(),
+            Activity::Waiting => "Waiting".to_string(),
+            Activity::Checking => "Checking for new frames".to_string(),
+            Activity::Watching => "Watching for new frames".to_string(),
+            Activity::Downloading(name) => format!("Downloading {}", name),
+            Activity::Downloaded(name) => format!("Downloaded {}", name),
+            Activity::Failed(e) => format!("Download failed: {}", e),
+            Activity::Unreachable(e) => format!("Server unreachable: {}", e),
+        };
+
+        match self.last_frame {
+            Some(time) => format!("{} (last frame {})", activity, time.format("%H:%M")),
+            None => activity,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// Least recently used cache of textures keyed by image path, limited to a memory budget.
+// Generic over the texture type so the eviction logic doesn't need an OpenGL context.
+
+use std::collections::HashMap;
+
+struct Entry<T> {
+    texture: T,
+    bytes: usize,
+    last_used: u64,
+}
+
+pub struct TextureCache<T> {
+    entries: HashMap<String, Entry<T>>,
+    budget: usize, // Bytes, 0 for no limit
+    used: usize,
+    clock: u64, // Incremented on every use, so the smallest last_used is the least recent
+}
+
+impl<T> TextureCache<T> {
+    pub fn new(budget: usize) -> Self {
+        TextureCache {
+            entries: HashMap::new(),
+            budget,
+            used: 0,
+            clock: 0,
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    // The texture for 'path', marking it as the most recently used
+    pub fn get(&mut self, path: &str) -> Option<&T> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(path).map(|entry| {
+            entry.last_used = clock;
+            &entry.texture
+        })
+    }
+
+    // The texture for 'path', without counting as a use
+    pub fn peek(&self, path: &str) -> Option<&T> {
+        self.entries.get(path).map(|entry| &entry.texture)
+    }
+
+    // Add a texture taking up 'bytes', evicting the least recently used textures to make room.
+    // A texture bigger than the whole budget is still kept, until the next insert
+    pub fn insert(&mut self, path: &str, texture: T, bytes: usize) {
+        self.remove(path);
+        if self.budget > 0 {
+            while self.used + bytes > self.budget && self.evict_oldest() {}
+        }
+
+        self.clock += 1;
+        self.used += bytes;
+        self.entries.insert(
+            path.to_string(),
+            Entry {
+                texture,
+                bytes,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    // Forget the texture for 'path', e.g. because the image changed on disk
+    pub fn remove(&mut self, path: &str) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.used -= entry.bytes;
+        }
+    }
+
+    // Remove the least recently used texture. Returns false if the cache was already empty
+    fn evict_oldest(&mut self) -> bool {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(path, _)| path.clone());
+
+        match oldest {
+            Some(path) => {
+                self.remove(&path);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_over_budget() {
+        let mut cache = TextureCache::new(30);
+        cache.insert("a", (), 10);
+        cache.insert("b", (), 10);
+        cache.insert("c", (), 10);
+        assert_eq!(cache.used, 30);
+
+        // Using 'a' makes 'b' the oldest
+        assert!(cache.get("a").is_some());
+        cache.insert("d", (), 10);
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+        assert!(cache.contains("d"));
+        assert_eq!(cache.used, 30);
+
+        // Making room for a big texture evicts several
+        cache.insert("e", (), 25);
+        assert_eq!(cache.used, 25);
+        assert!(cache.contains("e"));
+    }
+
+    #[test]
+    fn replacing_and_removing_keep_the_total() {
+        let mut cache = TextureCache::new(0);
+        cache.insert("a", (), 10);
+        cache.insert("a", (), 15);
+        assert_eq!(cache.used, 15);
+
+        cache.remove("a");
+        cache.remove("missing");
+        assert_eq!(cache.used, 0);
+        assert!(cache.get("a").is_none());
+
+        // No budget means no eviction
+        for name in &["a", "b", "c"] {
+            cache.insert(name, (), 1000);
+        }
+        assert_eq!(cache.used, 3000);
+    }
+}
@@ -1,55 +1,323 @@
 #[macro_use]
 extern crate glium;
+#[macro_use]
+extern crate serde;
+extern crate chrono;
+extern crate clap;
+extern crate ctrlc;
 extern crate ftp;
+extern crate image;
+extern crate png;
+extern crate toml;
+extern crate ureq;
 
 use std::sync::mpsc::channel;
-use std::env;
 use std::thread;
+use std::time::Duration;
+
+use clap::Parser;
 
+mod cli;
+mod config;
+mod daemon;
 mod downloader;
+mod error;
+mod event;
+mod frame_index;
 mod image_viewer;
+mod radar;
+mod retention;
+mod source;
+#[cfg(test)]
+mod temp_dir;
+mod timecode;
+mod verify;
+mod watcher;
 
-// Configuration constants
-const DL_DIR: &str = "img/"; // Folder to keep images in.
-const CODE_LOW: &str = "IDR042";
-const CODE_MID: &str = "IDR043"; // BOM product code for the desired radar image set
-const CODE_HIGH: &str = "IDR044";
-
-// Milliseconds per frame
-const SPEED_SLOW: usize = 200;
-const SPEED_MID: usize = 100;
-const SPEED_FAST: usize = 60;
+use cli::Cli;
+use cli::Command;
+use cli::ExportArgs;
+use cli::ViewArgs;
+use config::Config;
+use daemon::LockFile;
+use event::DownloadEvent;
+use frame_index::FrameIndex;
+use radar::RadarSite;
+use source::RadarSource;
 
 // Main function.
 fn main() {
-    let mut clean = false;
-
+    let cli = Cli::parse();
     println!("Radar Monitor:");
-    // Check the program args
-    for arg in env::args().skip(1) {
-        if arg == "--clean" || arg == "-c" {
-            clean = true;
-        } else {
-            println!("Unknown argument: {}", arg);
+
+    // Load the config file, then let the command line override it
+    let mut config = match Config::load(cli.overrides.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("Error loading config: {}", e);
             return;
         }
+    };
+    cli.overrides.apply(&mut config);
+
+    match cli.command.unwrap_or(Command::View(ViewArgs::default())) {
+        Command::View(args) => {
+            args.apply(&mut config);
+            if let Some(site) = find_site(&config) {
+                view(&site, &config, &args);
+            }
+        }
+        Command::Download => {
+            if let Some(site) = find_site(&config) {
+                download(&site, &config);
+            }
+        }
+        Command::Clean => {
+            if let Some(site) = find_site(&config) {
+                clean(&site, &config);
+            }
+        }
+        Command::Render { file, range } => {
+            if let Some(site) = find_site(&config) {
+                if let Some(zoom) = find_range(&site, range.as_deref()) {
+                    render(&site, &config, zoom, &file);
+                }
+            }
+        }
+        Command::Export(args) => {
+            if let Some(site) = find_site(&config) {
+                if let Some(zoom) = find_range(&site, args.range.as_deref()) {
+                    export(&site, &config, zoom, &args);
+                }
+            }
+        }
+        Command::List => list(&config),
+        Command::Sites => print_sites(),
+    }
+}
+
+// Download new frames in the background and animate them in a window. Offline, frames are only
+// read from the download folder
+fn view(site: &RadarSite, config: &Config, args: &ViewArgs) {
+    if args.clean {
+        clean(site, config);
     }
 
+    // Only one process can download into a folder at a time
+    let _lock = if args.offline {
+        None
+    } else {
+        match LockFile::acquire(&config.download_dir) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        }
+    };
+
     // Create some channels for communication
-    let (tx, rx) = channel::<f32>();
+    let (tx, rx) = channel();
 
-    if clean {
-        println!("Cleaning images directory");
-        downloader::clean();
-    }
+    let dl_site = site.clone();
+    let dl_config = config.clone();
+    if args.offline {
+        let index = match FrameIndex::scan(site, config) {
+            Ok(index) => index,
+            Err(e) => {
+                println!("Couldn't read {}: {}", config.download_dir, e);
+                return;
+            }
+        };
+        println!(
+            "Viewing {} ({}) from {}",
+            site.name,
+            site.codes().join(", "),
+            config.download_dir
+        );
 
-    downloader::init();
+        // Start the thread which looks for files saved by something else
+        thread::spawn(move || {
+            if let Err(e) = watcher::run(tx, index, &dl_site, &dl_config) {
+                println!("Watching stopped: {}", e);
+            }
+        });
+    } else {
+        // A kiosk opens with the frames on disk even if the downloader can't start, e.g. without a
+        // network at boot, and keeps trying to start it
+        let restart = config.kiosk.enabled;
+        let started = start_downloading(site, config);
+        if started.is_none() && !restart {
+            return;
+        }
 
-    // Start the thread which downloads the files
-    thread::spawn(move || {
-        downloader::run_loop(tx).expect("Downloading Error");
-    });
+        // Start the thread which downloads the files
+        thread::spawn(move || {
+            if let Err(e) = downloader::run_supervised(tx, started, &dl_site, &dl_config, restart) {
+                println!("Downloading stopped: {}", e);
+            }
+        });
+    }
 
     // Open the window. This has to happen on the main thread for reasons
-    image_viewer::open_window(rx).expect("Drawing Error");
+    if let Err(e) = image_viewer::open_window(rx, site, config, args.offline) {
+        println!("Drawing error: {}", e);
+    }
+}
+
+// Download new frames without a window until stopped
+fn download(site: &RadarSite, config: &Config) {
+    if let Err(e) = daemon::run(site, config) {
+        println!("Downloading stopped: {}", e);
+    }
+}
+
+// Connect to the source and fetch the background layers and any frames that are missing
+fn start_downloading(
+    site: &RadarSite,
+    config: &Config,
+) -> Option<(Box<dyn RadarSource + Send>, FrameIndex)> {
+    println!("Watching {} ({})", site.name, site.codes().join(", "));
+
+    let mut source = match source::from_config(config) {
+        Ok(source) => source,
+        Err(e) => {
+            println!("{}", e);
+            return None;
+        }
+    };
+
+    // The viewer isn't running yet, so problems are printed here. Downloads are shown by the
+    // downloader's own progress lines
+    let notify = |event| match event {
+        DownloadEvent::Error(e) | DownloadEvent::ServerUnreachable(e) => println!("\r{}", e),
+        DownloadEvent::Pruned(paths) => println!("\rPruned {} old frames", paths.len()),
+        _ => (),
+    };
+
+    match downloader::init(source.as_mut(), site, config, &notify) {
+        Ok(index) => Some((source, index)),
+        Err(e) => {
+            println!("Initialisation failure: {}", e);
+            None
+        }
+    }
+}
+
+fn clean(site: &RadarSite, config: &Config) {
+    println!("Cleaning images directory");
+    if let Err(e) = downloader::clean(site, config) {
+        println!("Error cleaning images directory: {}", e);
+    }
+}
+
+// Draw what's already on disk, without connecting to anything
+fn render(site: &RadarSite, config: &Config, zoom: usize, path: &str) {
+    match image_viewer::render_to_png(site, config, zoom, path) {
+        Ok(()) => println!("Saved {}", path),
+        Err(e) => println!("Render error: {}", e),
+    }
+}
+
+// Likewise write an animation of what's on disk
+fn export(site: &RadarSite, config: &Config, zoom: usize, args: &ExportArgs) {
+    let delay = args.delay.map(Duration::from_millis);
+    let (from, to) = (args.from, args.to);
+    match image_viewer::export_animation(site, config, zoom, from, to, delay, &args.file) {
+        Ok(0) => println!("No frames to export"),
+        Ok(frames) => println!("Saved {} frames to {}", frames, args.file),
+        Err(e) => println!("Export error: {}", e),
+    }
+}
+
+// Frames on disk for each range of each radar that has any
+fn list(config: &Config) {
+    let mut sites = RadarSite::known();
+    if let Some(site) = RadarSite::find(&config.site) {
+        if !sites.iter().any(|known| known.id == site.id) {
+            sites.push(site);
+        }
+    }
+
+    let mut found = false;
+    for site in sites {
+        // Skip anything unreadable rather than give up on the rest
+        let index = match FrameIndex::scan(&site, config) {
+            Ok(index) => index,
+            Err(_) => continue,
+        };
+        if site
+            .codes()
+            .iter()
+            .all(|code| index.frames(code).is_empty())
+        {
+            continue;
+        }
+
+        found = true;
+        println!("{} {}", site.id, site.name);
+        for range in &site.ranges {
+            let frames = index.frames(&range.code);
+            match (frames.first(), frames.last()) {
+                (Some(oldest), Some(newest)) => println!(
+                    "  {} {}km: {} frames, {} to {} UTC",
+                    range.code,
+                    range.km,
+                    frames.len(),
+                    oldest.time,
+                    newest.time
+                ),
+                _ => println!("  {} {}km: no frames", range.code, range.km),
+            }
+        }
+    }
+
+    if !found {
+        println!("No frames in {}", config.download_dir);
+    }
+}
+
+// The site picked in the config, or None after listing the known ones if it isn't valid
+fn find_site(config: &Config) -> Option<RadarSite> {
+    let site = RadarSite::find(&config.site);
+    if site.is_none() {
+        println!("Unknown radar site: '{}'. Known sites are:", config.site);
+        print_sites();
+    }
+    site
+}
+
+// The range asked for on the command line, or the one the viewer opens at if none was
+fn find_range(site: &RadarSite, query: Option<&str>) -> Option<usize> {
+    let query = match query {
+        Some(query) => query,
+        None => return Some(site.default_range()),
+    };
+
+    let zoom = site.find_range(query);
+    if zoom.is_none() {
+        let ranges: Vec<_> = site
+            .ranges
+            .iter()
+            .map(|range| format!("{} ({}km)", range.code, range.km))
+            .collect();
+        println!(
+            "Unknown range for {}: '{}'. It has {}",
+            site.name,
+            query,
+            ranges.join(", ")
+        );
+    }
+    zoom
+}
+
+fn print_sites() {
+    for site in RadarSite::known() {
+        let ranges: Vec<_> = site
+            .ranges
+            .iter()
+            .map(|range| format!("{}km", range.km))
+            .collect();
+        println!("  {} {} ({})", site.id, site.name, ranges.join(", "));
+    }
 }
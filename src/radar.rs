@@ -0,0 +1,158 @@
+// Radar site definitions.
+// A site is a single BOM radar which publishes images at several ranges (zoom levels). Each range
+// has its own product code made from the site id and a range digit, e.g. IDR043 is the 128km
+// image from site 04 (Newcastle).
+
+// Known sites as (id, name, available range digits)
+const KNOWN_SITES: &[(&str, &str, &[usize])] = &[
+    ("02", "Melbourne", &[2, 3, 4]),
+    ("03", "Wollongong", &[2, 3, 4]),
+    ("04", "Newcastle", &[2, 3, 4]),
+    ("40", "Canberra", &[2, 3, 4]),
+    ("63", "Darwin", &[2, 3, 4]),
+    ("64", "Adelaide", &[2, 3, 4]),
+    ("66", "Brisbane", &[2, 3, 4]),
+    ("70", "Perth", &[2, 3, 4]),
+    ("71", "Sydney", &[2, 3, 4]),
+    ("76", "Hobart", &[2, 3, 4]),
+];
+
+// Site used when none is requested
+pub const DEFAULT_SITE: &str = "04";
+
+// Ranges assumed for a site id that isn't in the table above
+const DEFAULT_RANGES: &[usize] = &[2, 3, 4];
+
+#[derive(Clone, Debug)]
+pub struct RadarRange {
+    pub code: String, // BOM product code, e.g. IDR043
+    pub km: usize,    // Radius of the image in kilometres
+}
+
+#[derive(Clone, Debug)]
+pub struct RadarSite {
+    pub id: String,              // Two digit BOM site id, e.g. 04
+    pub name: String,            // Human readable name
+    pub ranges: Vec<RadarRange>, // Ordered from widest to narrowest
+}
+
+impl RadarSite {
+    pub fn new(id: &str, name: &str, range_digits: &[usize]) -> Self {
+        RadarSite {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            ranges: range_digits
+                .iter()
+                .map(|&digit| RadarRange {
+                    code: format!("IDR{}{}", id, digit),
+                    km: range_km(digit),
+                })
+                .collect(),
+        }
+    }
+
+    // Every site in the known sites table
+    pub fn known() -> Vec<Self> {
+        KNOWN_SITES
+            .iter()
+            .map(|&(id, name, ranges)| RadarSite::new(id, name, ranges))
+            .collect()
+    }
+
+    // Look up a site by id ("04"), product prefix ("IDR04") or name ("newcastle").
+    // Any other two digit id is accepted as an unnamed site with the default ranges.
+    pub fn find(query: &str) -> Option<Self> {
+        let query = query.trim();
+        let id = query.trim_start_matches("IDR").trim_start_matches("idr");
+
+        if let Some(site) = RadarSite::known()
+            .into_iter()
+            .find(|site| site.id == id || site.name.eq_ignore_ascii_case(query))
+        {
+            return Some(site);
+        }
+
+        if id.len() == 2 && id.chars().all(|c| c.is_ascii_digit()) {
+            Some(RadarSite::new(id, &format!("IDR{}", id), DEFAULT_RANGES))
+        } else {
+            None
+        }
+    }
+
+    // Product codes for each range of this site
+    pub fn codes(&self) -> Vec<&str> {
+        self.ranges
+            .iter()
+            .map(|range| range.code.as_str())
+            .collect()
+    }
+
+    // Index of the range shown when nothing else is asked for, the middle one
+    pub fn default_range(&self) -> usize {
+        self.ranges.len() / 2
+    }
+
+    // Index of the range with product code ("IDR043") or radius ("128", "128km") 'query'
+    pub fn find_range(&self, query: &str) -> Option<usize> {
+        let query = query.trim();
+        let km = query
+            .trim_end_matches("km")
+            .trim_end_matches("KM")
+            .parse()
+            .ok();
+
+        self.ranges
+            .iter()
+            .position(|range| range.code.eq_ignore_ascii_case(query) || Some(range.km) == km)
+    }
+}
+
+// The last digit of a product code determines the radius of the image
+fn range_km(digit: usize) -> usize {
+    match digit {
+        1 => 512,
+        2 => 256,
+        3 => 128,
+        4 => 64,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sites_are_found_by_id_prefix_or_name() {
+        for query in &["04", "IDR04", "idr04", " 04 ", "Newcastle", "NEWCASTLE"] {
+            let site = RadarSite::find(query).unwrap();
+            assert_eq!(site.id, "04");
+            assert_eq!(site.name, "Newcastle");
+        }
+        assert!(RadarSite::find(DEFAULT_SITE).is_some());
+        assert!(RadarSite::find("Atlantis").is_none());
+        assert!(RadarSite::find("123").is_none());
+    }
+
+    #[test]
+    fn unknown_ids_get_the_default_ranges() {
+        let site = RadarSite::find("IDR99").unwrap();
+        assert_eq!(site.id, "99");
+        assert_eq!(site.name, "IDR99");
+        assert_eq!(site.codes(), vec!["IDR992", "IDR993", "IDR994"]);
+        let km: Vec<_> = site.ranges.iter().map(|range| range.km).collect();
+        assert_eq!(km, vec![256, 128, 64]);
+    }
+
+    #[test]
+    fn ranges_are_found_by_code_or_km() {
+        let site = RadarSite::new("04", "Newcastle", &[2, 3, 4]);
+        assert_eq!(site.default_range(), 1);
+        assert_eq!(site.find_range("IDR042"), Some(0));
+        assert_eq!(site.find_range("idr044"), Some(2));
+        assert_eq!(site.find_range("128km"), Some(1));
+        assert_eq!(site.find_range("64"), Some(2));
+        assert_eq!(site.find_range("512km"), None);
+        assert_eq!(site.find_range("IDR033"), None);
+    }
+}
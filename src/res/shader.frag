@@ -5,8 +5,11 @@ in vec2 Texture_pos;
 out vec4 outColor;
 
 uniform sampler2D tex;
+uniform sampler2D next_tex; // Crossfaded in by blend
+uniform float blend;
+uniform vec4 colour;
 
 void main()
 {
-    outColor = texture(tex, Texture_pos);
+    outColor = mix(texture(tex, Texture_pos), texture(next_tex, Texture_pos), blend) * colour;
 }
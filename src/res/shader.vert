@@ -4,13 +4,13 @@ in vec2 position;
 in vec2 texture_pos;
 
 uniform mat4 matrix;
+uniform vec4 tex_rect; // Part of the texture to draw as (left, bottom, width, height)
 
 out vec2 Texture_pos;
 
 void main()
 {
-    Texture_pos = texture_pos;
+    Texture_pos = tex_rect.xy + texture_pos * tex_rect.zw;
 
     gl_Position = matrix * vec4(position, 0.0, 1.0);
 }
-
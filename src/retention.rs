@@ -0,0 +1,160 @@
+// Retention policy for downloaded images.
+// Frames are pruned oldest first once they're older than the maximum age, or there are more of
+// them than the maximum count or total size. Each range of a site is pruned separately.
+
+use chrono::Duration;
+use chrono::Utc;
+use std::fs;
+
+use config::Config;
+use config::RetentionConfig;
+use downloader::file_names_in;
+use error::Result;
+use radar::RadarSite;
+use timecode::Timecode;
+
+// Apply the retention policy to every range of 'site'.
+// Returns the paths of the files that were removed.
+pub fn prune(site: &RadarSite, config: &Config) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    for lc_code in site.codes() {
+        removed.append(&mut prune_dir(
+            &config.image_dir(lc_code),
+            &config.retention,
+        )?);
+    }
+    Ok(removed)
+}
+
+// Apply the retention policy to the images in 'dir'. Files that aren't radar images are left alone
+fn prune_dir(dir: &str, policy: &RetentionConfig) -> Result<Vec<String>> {
+    // Pair each image with its time, newest first
+    let mut frames: Vec<_> = file_names_in(dir)?
+        .into_iter()
+        .filter_map(|name| {
+            Timecode::from_file_name(&name)
+                .ok()
+                .map(|time| (time, name))
+        })
+        .collect();
+    frames.sort();
+    frames.reverse();
+
+    let now = Utc::now();
+    let max_age = Duration::hours(policy.max_age_hours);
+    let max_bytes = policy.max_megabytes * 1024 * 1024;
+
+    let mut bytes = 0;
+    let mut removed = Vec::new();
+    for (count, (time, name)) in frames.iter().enumerate() {
+        let path = dir.to_string() + name;
+        bytes += fs::metadata(&path)?.len();
+
+        let keep = (policy.max_age_hours == 0 || now - time.datetime() <= max_age)
+            && (policy.max_frames == 0 || count < policy.max_frames)
+            && (policy.max_megabytes == 0 || bytes <= max_bytes);
+
+        if !keep {
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use temp_dir::TempDir;
+
+    // Create a directory of frames for test 'name', one for each of 'times' with 'size' bytes.
+    // Returns the directory, and its path with a trailing slash
+    fn frames_dir(name: &str, times: &[Timecode], size: usize) -> (TempDir, String) {
+        let temp = TempDir::new(name);
+        let dir = temp.join("");
+        for time in times {
+            fs::write(format!("{}IDR043.T.{}.png", dir, time), vec![0; size]).unwrap();
+        }
+        fs::write(dir.clone() + "notes.txt", "not a frame").unwrap();
+        (temp, dir)
+    }
+
+    // Times of the frames left in 'dir'
+    fn remaining(dir: &str) -> Vec<String> {
+        let mut names: Vec<_> = file_names_in(dir)
+            .unwrap()
+            .iter()
+            .filter_map(|name| Timecode::from_file_name(name).ok())
+            .map(|time| time.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn policy(max_age_hours: i64, max_frames: usize, max_megabytes: u64) -> RetentionConfig {
+        RetentionConfig {
+            max_age_hours,
+            max_frames,
+            max_megabytes,
+        }
+    }
+
+    #[test]
+    fn prunes_by_count() {
+        let times: Vec<Timecode> = ["201901010000", "201901010005", "201901010010"]
+            .iter()
+            .map(|time| time.parse().unwrap())
+            .collect();
+        let (_temp, dir) = frames_dir("prune_count", &times, 1);
+
+        let removed = prune_dir(&dir, &policy(0, 2, 0)).unwrap();
+
+        assert_eq!(removed, vec![dir.clone() + "IDR043.T.201901010000.png"]);
+        assert_eq!(remaining(&dir), vec!["201901010005", "201901010010"]);
+        assert!(file_names_in(&dir)
+            .unwrap()
+            .contains(&"notes.txt".to_string()));
+    }
+
+    #[test]
+    fn keeps_everything_by_default() {
+        let times: Vec<Timecode> = ["201001010000", "201901010000"]
+            .iter()
+            .map(|time| time.parse().unwrap())
+            .collect();
+        let (_temp, dir) = frames_dir("prune_default", &times, 1);
+
+        assert!(prune_dir(&dir, &RetentionConfig::default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(remaining(&dir), vec!["201001010000", "201901010000"]);
+    }
+
+    #[test]
+    fn prunes_by_age() {
+        let now: Timecode = Utc::now().format("%Y%m%d%H%M").to_string().parse().unwrap();
+        let times = [now + Duration::hours(-3), now + Duration::hours(-1), now];
+        let (_temp, dir) = frames_dir("prune_age", &times, 1);
+
+        prune_dir(&dir, &policy(2, 0, 0)).unwrap();
+
+        assert_eq!(
+            remaining(&dir),
+            vec![times[1].to_string(), times[2].to_string()]
+        );
+    }
+
+    #[test]
+    fn prunes_by_size() {
+        let times: Vec<Timecode> = ["201901010000", "201901010005", "201901010010"]
+            .iter()
+            .map(|time| time.parse().unwrap())
+            .collect();
+        let (_temp, dir) = frames_dir("prune_size", &times, 400 * 1024);
+
+        prune_dir(&dir, &policy(0, 0, 1)).unwrap();
+
+        assert_eq!(remaining(&dir), vec!["201901010005", "201901010010"]);
+    }
+}
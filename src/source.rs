@@ -0,0 +1,49 @@
+// Sources of radar images.
+// The downloader only needs to be able to list the frames a source has available and fetch them by
+// name, so each backend (FTP server, web server, local folder, ...) implements RadarSource and the downloader
+// doesn't care where the images come from.
+
+use config::Config;
+use error::Error;
+use error::Result;
+
+mod ftp;
+mod http;
+mod local;
+#[cfg(test)]
+pub mod mock;
+
+pub use self::ftp::FtpSource;
+pub use self::http::HttpSource;
+pub use self::local::LocalSource;
+
+pub trait RadarSource {
+    // File names of every radar image currently available
+    fn list_frames(&mut self) -> Result<Vec<String>>;
+
+    // Contents of the radar image 'name' as returned by list_frames
+    fn fetch_frame(&mut self, name: &str) -> Result<Vec<u8>>;
+
+    // Contents of the transparency layer 'name', e.g. IDR043.background.png
+    fn fetch_transparency(&mut self, name: &str) -> Result<Vec<u8>>;
+
+    // Called when a batch of requests is done, so connections can be closed between batches
+    fn finish(&mut self) {}
+}
+
+// Create the source selected by the config
+pub fn from_config(config: &Config) -> Result<Box<dyn RadarSource + Send>> {
+    match config.source.as_str() {
+        "ftp" => Ok(Box::new(FtpSource::new(&config.server))),
+        "http" if config.http.radar_url.is_empty() => Err(Error::Parse(
+            "The http source needs http.radar_url, a directory listing of the radar images"
+                .to_string(),
+        )),
+        "http" => Ok(Box::new(HttpSource::new(&config.http))),
+        "local" => Ok(Box::new(LocalSource::new(
+            &config.local.radar_dir,
+            &config.local.transparencies_dir,
+        ))),
+        other => Err(Error::Parse(format!("Unknown image source: '{}'", other))),
+    }
+}
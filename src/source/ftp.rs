@@ -0,0 +1,73 @@
+use ftp::FtpStream;
+
+use super::RadarSource;
+use config::ServerConfig;
+use error::Result;
+
+// Images from an FTP server such as the BOM's. The connection is opened on the first request and
+// kept until finish() is called.
+pub struct FtpSource {
+    server: ServerConfig,
+    stream: Option<FtpStream>,
+    dir: String, // Directory the stream is currently in
+}
+
+impl FtpSource {
+    pub fn new(server: &ServerConfig) -> Self {
+        FtpSource {
+            server: server.clone(),
+            stream: None,
+            dir: String::new(),
+        }
+    }
+
+    // Get a connected stream in directory 'dir', connecting and logging in if required
+    fn stream(&mut self, dir: &str) -> Result<&mut FtpStream> {
+        if self.stream.is_none() {
+            let mut ftp_stream = FtpStream::connect(&self.server.host)?;
+            ftp_stream.login(&self.server.user, &self.server.password)?;
+            self.stream = Some(ftp_stream);
+            self.dir.clear();
+        }
+
+        let ftp_stream = self
+            .stream
+            .as_mut()
+            .expect("FTP stream missing after connect");
+        if self.dir != dir {
+            ftp_stream.cwd(dir)?;
+            self.dir = dir.to_string();
+        }
+
+        Ok(ftp_stream)
+    }
+
+    fn retrieve(&mut self, dir: &str, name: &str) -> Result<Vec<u8>> {
+        let file = self.stream(dir)?.simple_retr(name)?;
+        Ok(file.into_inner())
+    }
+}
+
+impl RadarSource for FtpSource {
+    fn list_frames(&mut self) -> Result<Vec<String>> {
+        let dir = self.server.radar_dir.clone();
+        Ok(self.stream(&dir)?.nlst(None)?)
+    }
+
+    fn fetch_frame(&mut self, name: &str) -> Result<Vec<u8>> {
+        let dir = self.server.radar_dir.clone();
+        self.retrieve(&dir, name)
+    }
+
+    fn fetch_transparency(&mut self, name: &str) -> Result<Vec<u8>> {
+        let dir = self.server.transparencies_dir.clone();
+        self.retrieve(&dir, name)
+    }
+
+    fn finish(&mut self) {
+        // Disconnect from the server
+        if let Some(mut ftp_stream) = self.stream.take() {
+            let _ = ftp_stream.quit();
+        }
+    }
+}
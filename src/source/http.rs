@@ -0,0 +1,189 @@
+use std::io::Read;
+use std::time::Duration;
+
+use ureq::Agent;
+use ureq::AgentBuilder;
+
+use super::RadarSource;
+use config::HttpConfig;
+use error::Result;
+
+// Images from a web server. The radar folder must serve a directory listing, which is scanned for
+// links to find the available frames.
+pub struct HttpSource {
+    agent: Agent,
+    radar_url: String,
+    transparencies_url: String,
+}
+
+impl HttpSource {
+    pub fn new(http: &HttpConfig) -> Self {
+        HttpSource {
+            agent: AgentBuilder::new()
+                .timeout(Duration::from_secs(http.timeout_secs))
+                .build(),
+            radar_url: with_trailing_slash(&http.radar_url),
+            transparencies_url: with_trailing_slash(&http.transparencies_url),
+        }
+    }
+
+    fn get(&self, url: &str) -> Result<ureq::Response> {
+        Ok(self.agent.get(url).call()?)
+    }
+
+    fn get_bytes(&self, url: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.get(url)?.into_reader().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl RadarSource for HttpSource {
+    fn list_frames(&mut self) -> Result<Vec<String>> {
+        let listing = self.get(&self.radar_url)?.into_string()?;
+        Ok(parse_listing(&listing))
+    }
+
+    fn fetch_frame(&mut self, name: &str) -> Result<Vec<u8>> {
+        self.get_bytes(&(self.radar_url.clone() + name))
+    }
+
+    fn fetch_transparency(&mut self, name: &str) -> Result<Vec<u8>> {
+        self.get_bytes(&(self.transparencies_url.clone() + name))
+    }
+}
+
+// Pull the file names out of every link in an HTML directory listing.
+// Links to other folders, sorting links (?C=M;O=A) and the parent directory are skipped.
+fn parse_listing(listing: &str) -> Vec<String> {
+    let mut names: Vec<String> = listing
+        .split("href=")
+        .skip(1)
+        .filter_map(|rest| {
+            let quote = rest.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+            let link = rest[1..].split(quote).next()?;
+            let name = link.split(['?', '#']).next()?.rsplit('/').next()?;
+            if name.is_empty() || name.starts_with('.') {
+                None
+            } else {
+                Some(name.to_string())
+            }
+        })
+        .collect();
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn with_trailing_slash(url: &str) -> String {
+    if url.ends_with('/') {
+        url.to_string()
+    } else {
+        url.to_string() + "/"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::Error;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // Serve 'count' requests on a local port, answering each path from 'pages'.
+    // Returns the base url of the server.
+    fn serve(pages: Vec<(&'static str, &'static [u8])>, count: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+                let path = request.split(' ').nth(1).unwrap_or("").to_string();
+
+                let response = match pages.iter().find(|(page, _)| *page == path) {
+                    Some((_, body)) => {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(body);
+                        response
+                    }
+                    None => {
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_vec()
+                    }
+                };
+                stream.write_all(&response).unwrap();
+            }
+        });
+
+        url
+    }
+
+    fn source_for(url: &str) -> HttpSource {
+        HttpSource::new(&HttpConfig {
+            radar_url: url.to_string() + "radar",
+            transparencies_url: url.to_string() + "transparencies/",
+            timeout_secs: 5,
+        })
+    }
+
+    #[test]
+    fn parse_listing_finds_file_links() {
+        let listing = r#"<html><body>
+            <a href="?C=N;O=D">Name</a>
+            <a href="/anon/gen/">Parent Directory</a>
+            <a href="IDR043.T.201901010000.png">IDR043.T.201901010000.png</a>
+            <a href='/anon/gen/radar/IDR043.gif'>IDR043.gif</a>
+            <a href="IDR043.T.201901010000.png">again</a>
+            </body></html>"#;
+
+        assert_eq!(
+            parse_listing(listing),
+            vec!["IDR043.T.201901010000.png", "IDR043.gif"]
+        );
+    }
+
+    #[test]
+    fn fetches_from_local_server() {
+        let url = serve(
+            vec![
+                ("/radar/", br#"<a href="IDR043.T.201901010000.png">x</a>"#),
+                ("/radar/IDR043.T.201901010000.png", b"frame"),
+                ("/transparencies/IDR043.background.png", b"background"),
+            ],
+            4,
+        );
+        let mut source = source_for(&url);
+
+        assert_eq!(
+            source.list_frames().unwrap(),
+            vec!["IDR043.T.201901010000.png"]
+        );
+        assert_eq!(
+            source.fetch_frame("IDR043.T.201901010000.png").unwrap(),
+            b"frame"
+        );
+        assert_eq!(
+            source.fetch_transparency("IDR043.background.png").unwrap(),
+            b"background"
+        );
+        match source.fetch_frame("IDR043.T.201901010005.png") {
+            Err(Error::Http(e)) => match *e {
+                ureq::Error::Status(status, _) => assert_eq!(status, 404),
+                other => panic!("Unexpected error {}", other),
+            },
+            other => panic!("Expected a 404, got {:?}", other.map(|_| ())),
+        }
+    }
+}
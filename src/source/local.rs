@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::RadarSource;
+use error::Result;
+
+// Images from folders on the local file system, laid out like the server: one folder of radar
+// images and one of transparency layers.
+pub struct LocalSource {
+    radar_dir: PathBuf,
+    transparencies_dir: PathBuf,
+}
+
+impl LocalSource {
+    pub fn new(radar_dir: &str, transparencies_dir: &str) -> Self {
+        LocalSource {
+            radar_dir: PathBuf::from(radar_dir),
+            transparencies_dir: PathBuf::from(transparencies_dir),
+        }
+    }
+}
+
+impl RadarSource for LocalSource {
+    fn list_frames(&mut self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.radar_dir)? {
+            if let Ok(name) = entry?.file_name().into_string() {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn fetch_frame(&mut self, name: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.radar_dir.join(name))?)
+    }
+
+    fn fetch_transparency(&mut self, name: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.transparencies_dir.join(name))?)
+    }
+}
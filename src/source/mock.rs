@@ -0,0 +1,61 @@
+use image::ImageFormat;
+use image::RgbaImage;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Cursor;
+
+use super::RadarSource;
+use error::Result;
+
+// In memory source for tests. Counts fetches so tests can check what was downloaded.
+#[derive(Default)]
+pub struct MockSource {
+    pub frames: BTreeMap<String, Vec<u8>>,
+    pub transparencies: BTreeMap<String, Vec<u8>>,
+    pub fetches: usize,
+}
+
+impl MockSource {
+    // Source with a valid image for each name
+    pub fn with_frames(names: &[&str]) -> Self {
+        let mut source = MockSource::default();
+        for name in names {
+            source.add_frame(name, &png());
+        }
+        source
+    }
+
+    pub fn add_frame(&mut self, name: &str, contents: &[u8]) {
+        self.frames.insert(name.to_string(), contents.to_vec());
+    }
+}
+
+impl RadarSource for MockSource {
+    fn list_frames(&mut self) -> Result<Vec<String>> {
+        Ok(self.frames.keys().cloned().collect())
+    }
+
+    fn fetch_frame(&mut self, name: &str) -> Result<Vec<u8>> {
+        self.fetches += 1;
+        self.frames
+            .get(name)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_string()).into())
+    }
+
+    fn fetch_transparency(&mut self, name: &str) -> Result<Vec<u8>> {
+        self.transparencies
+            .get(name)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_string()).into())
+    }
+}
+
+// A small valid PNG
+pub fn png() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    RgbaImage::new(2, 2)
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .expect("Error encoding test image");
+    bytes
+}
@@ -0,0 +1,39 @@
+// Scratch folders for tests. Each test gets its own, named after the test and the process so
+// test runs don't trip over each other, and it's removed again when the test is done with it.
+
+use std::env;
+use std::fs;
+use std::process;
+
+pub struct TempDir {
+    path: String,
+}
+
+impl TempDir {
+    // Create an empty folder for test 'name', replacing anything left by an earlier run
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("radar_monitor_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        TempDir {
+            path: path.to_str().unwrap().to_string(),
+        }
+    }
+
+    // Path of the folder, without a trailing slash
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Path of 'name' inside the folder
+    pub fn join(&self, name: &str) -> String {
+        format!("{}/{}", self.path, name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
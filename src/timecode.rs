@@ -0,0 +1,130 @@
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use std::fmt;
+use std::ops::Add;
+use std::ops::Sub;
+use std::str::FromStr;
+
+use error::Error;
+use error::Result;
+
+// Format of the timestamp in BOM file names, e.g. 201901312355 for 23:55 UTC on 31/1/2019
+const FORMAT: &str = "%Y%m%d%H%M";
+
+// The UTC time a radar image was taken, as given in its file name (IDR043.T.201901312355.png).
+// Ordered, and subtracting two gives the time between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timecode(DateTime<Utc>);
+
+impl Timecode {
+    // Timecode of a BOM radar image file name
+    pub fn from_file_name(name: &str) -> Result<Self> {
+        // Split at each dot and take the third (just the timecode)
+        name.split('.')
+            .nth(2)
+            .ok_or_else(|| Error::Parse(format!("Unexpected file name: {}", name)))?
+            .parse()
+    }
+
+    pub fn datetime(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+impl FromStr for Timecode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // chrono accepts some variations in field width, so insist on exactly the BOM format
+        if s.len() != 12 || !s.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::Parse(format!("Unexpected timecode: {}", s)));
+        }
+
+        NaiveDateTime::parse_from_str(s, FORMAT)
+            .map(|time| Timecode(time.and_utc()))
+            .map_err(|e| Error::Parse(format!("Unexpected timecode {}: {}", s, e)))
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.format(FORMAT))
+    }
+}
+
+impl Sub for Timecode {
+    type Output = Duration;
+
+    fn sub(self, other: Timecode) -> Duration {
+        self.0 - other.0
+    }
+}
+
+impl Add<Duration> for Timecode {
+    type Output = Timecode;
+
+    fn add(self, duration: Duration) -> Timecode {
+        Timecode(self.0 + duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tc(s: &str) -> Timecode {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_format_round_trip() {
+        let name = "IDR043.T.201901312355.png";
+        let timecode = Timecode::from_file_name(name).unwrap();
+
+        assert_eq!(timecode.to_string(), "201901312355");
+        assert_eq!(format!("IDR043.T.{}.png", timecode), name);
+    }
+
+    #[test]
+    fn rejects_unexpected_names() {
+        assert!(Timecode::from_file_name("IDR043.background.png").is_err());
+        assert!(Timecode::from_file_name("IDR043.gif").is_err());
+        assert!(Timecode::from_file_name("IDR043.T.2019013123.png").is_err());
+        assert!(Timecode::from_file_name("IDR043.T.201902300000.png").is_err());
+        assert!(Timecode::from_file_name("IDR043.T.+20190131235.png").is_err());
+    }
+
+    #[test]
+    fn ordering_and_subtraction() {
+        assert!(tc("201901010000") < tc("201901010005"));
+        assert!(tc("201812312355") < tc("201901010000"));
+        assert_eq!(
+            tc("201901010005") - tc("201901010000"),
+            Duration::minutes(5)
+        );
+        assert_eq!(
+            tc("201901010000") - tc("201901010005"),
+            Duration::minutes(-5)
+        );
+        assert_eq!(
+            tc("201901010000") + Duration::minutes(5),
+            tc("201901010005")
+        );
+    }
+
+    #[test]
+    fn subtraction_across_boundaries() {
+        let five = Duration::minutes(5);
+        // Hour, day, month and year
+        assert_eq!(tc("201901010100") - tc("201901010055"), five);
+        assert_eq!(tc("201901020000") - tc("201901012355"), five);
+        assert_eq!(tc("201902010000") - tc("201901312355"), five);
+        assert_eq!(tc("201901010000") - tc("201812312355"), five);
+        // Leap and non leap years
+        assert_eq!(tc("202002290000") - tc("202002282355"), five);
+        assert_eq!(tc("202003010000") - tc("202002292355"), five);
+        assert_eq!(tc("201903010000") - tc("201902282355"), five);
+    }
+}
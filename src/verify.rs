@@ -0,0 +1,133 @@
+// Checks that downloaded images are complete, and writes them so that a partial file is never
+// left under a real image name.
+
+use image::ImageFormat;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+
+use config::Config;
+use downloader::file_names_in;
+use error::decode_error;
+use error::Result;
+use frame_index::FrameIndex;
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Every PNG ends with an empty IEND chunk: zero length, type, then a fixed CRC
+const PNG_END: &[u8] = &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82];
+
+// Suffix of files that are still being written
+const PARTIAL_SUFFIX: &str = ".part";
+
+// Folder (inside the download directory) corrupt images are moved to
+const QUARANTINE_DIR: &str = "quarantine";
+
+// Fully decode 'bytes' to check they're a valid PNG. 'name' is used for the error message
+pub fn verify_png(name: &str, bytes: &[u8]) -> Result<()> {
+    if !looks_complete(bytes) {
+        return Err(decode_error(name, "Incomplete PNG"));
+    }
+    image::load_from_memory_with_format(bytes, ImageFormat::Png)
+        .map(|_| ())
+        .map_err(|e| decode_error(name, e))
+}
+
+// Cheap check that 'bytes' are a whole PNG, i.e. the transfer wasn't cut short
+fn looks_complete(bytes: &[u8]) -> bool {
+    bytes.starts_with(PNG_SIGNATURE) && bytes.ends_with(PNG_END)
+}
+
+// Write 'bytes' to 'path' by writing a temporary file next to it and renaming it into place,
+// so anything reading 'path' sees either the old file or the whole new one
+pub fn write_atomic(path: &str, bytes: &[u8]) -> Result<()> {
+    let partial_path = partial_path(path);
+
+    let mut file = File::create(&partial_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&partial_path, path)?;
+
+    Ok(())
+}
+
+// Temporary name for 'path' while it's being written. The leading dot keeps it from being
+// mistaken for a radar image
+fn partial_path(path: &str) -> String {
+    let split = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let (dir, name) = path.split_at(split);
+    format!("{}.{}{}", dir, name, PARTIAL_SUFFIX)
+}
+
+// Check every frame in the index, moving any that are corrupt into the quarantine folder and
+// dropping them from the index so they're downloaded again. Also removes any partial files left
+// behind by an interrupted download. Returns the paths of the quarantined frames.
+pub fn quarantine_corrupt(
+    index: &mut FrameIndex,
+    lc_codes: &[&str],
+    config: &Config,
+) -> Result<Vec<String>> {
+    let quarantine = format!(
+        "{}/{}/",
+        config.download_dir.trim_end_matches('/'),
+        QUARANTINE_DIR
+    );
+    let mut corrupt = Vec::new();
+
+    for lc_code in lc_codes {
+        let dir = config.image_dir(lc_code);
+        for name in file_names_in(&dir)? {
+            if name.ends_with(PARTIAL_SUFFIX) {
+                fs::remove_file(dir.clone() + &name)?;
+            }
+        }
+
+        for frame in index.frames(lc_code) {
+            if !looks_complete(&fs::read(&frame.path)?) {
+                corrupt.push(frame.path.clone());
+            }
+        }
+    }
+
+    if !corrupt.is_empty() {
+        fs::create_dir_all(&quarantine)?;
+        for path in &corrupt {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            fs::rename(path, quarantine.clone() + name)?;
+        }
+        index.remove_paths(&corrupt);
+    }
+
+    Ok(corrupt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::mock::png;
+    use temp_dir::TempDir;
+
+    #[test]
+    fn verify_png_rejects_truncated_files() {
+        let bytes = png();
+        assert!(verify_png("good.png", &bytes).is_ok());
+        assert!(verify_png("short.png", &bytes[..bytes.len() - 1]).is_err());
+        assert!(verify_png("half.png", &bytes[..bytes.len() / 2]).is_err());
+        assert!(verify_png("empty.png", &[]).is_err());
+        assert!(verify_png("gif.png", b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn write_atomic_leaves_no_partial_file() {
+        let temp = TempDir::new("atomic");
+        let dir = temp.join("");
+
+        write_atomic(&(dir.clone() + "IDR043.T.201901010000.png"), b"data").unwrap();
+
+        assert_eq!(
+            file_names_in(&dir).unwrap(),
+            vec!["IDR043.T.201901010000.png"]
+        );
+        assert_eq!(partial_path("img/IDR043/a.png"), "img/IDR043/.a.png.part");
+    }
+}
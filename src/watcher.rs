@@ -0,0 +1,182 @@
+// Stand in for the downloader when viewing a folder that something else downloads to, or an
+// archive that isn't being added to at all. The folder is scanned every so often and changes are
+// announced to the viewer with the same events the downloader sends.
+// Collectors write each frame under a temporary name and rename it into place, so any frame the
+// scan finds is complete.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::mpsc::Sender;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use config::Config;
+use error::Error;
+use error::Result;
+use event::DownloadEvent;
+use frame_index::Frame;
+use frame_index::FrameIndex;
+use radar::RadarSite;
+
+// Watch the folders for 'site', starting from the frames in 'index'. Only returns once the
+// viewer has gone away
+pub fn run(
+    sender: Sender<DownloadEvent>,
+    mut index: FrameIndex,
+    site: &RadarSite,
+    config: &Config,
+) -> Result<()> {
+    match watch(&sender, &mut index, site, config) {
+        Err(Error::Disconnected) => Ok(()),
+        result => result,
+    }
+}
+
+fn watch(
+    sender: &Sender<DownloadEvent>,
+    index: &mut FrameIndex,
+    site: &RadarSite,
+    config: &Config,
+) -> Result<()> {
+    let mut backgrounds = background_times(site, config);
+    send(sender, DownloadEvent::Watching)?;
+
+    loop {
+        sleep(Duration::from_secs(config.timing.watch_secs.max(1)));
+
+        let latest = match FrameIndex::scan(site, config) {
+            Ok(latest) => latest,
+            Err(e) => {
+                println!("\rCouldn't read the download folder: {}", e);
+                send(sender, DownloadEvent::Error(e))?;
+                continue;
+            }
+        };
+
+        let (added, removed) = changes(index, &latest, site);
+        for frame in added {
+            send(sender, DownloadEvent::NewFrame(frame))?;
+        }
+        if !removed.is_empty() {
+            send(sender, DownloadEvent::Pruned(removed))?;
+        }
+        *index = latest;
+
+        let latest_backgrounds = background_times(site, config);
+        for (lc_code, modified) in &latest_backgrounds {
+            if backgrounds.get(lc_code) != Some(modified) {
+                send(sender, DownloadEvent::BackgroundRefreshed(lc_code.clone()))?;
+            }
+        }
+        backgrounds = latest_backgrounds;
+    }
+}
+
+fn send(sender: &Sender<DownloadEvent>, event: DownloadEvent) -> Result<()> {
+    sender.send(event).map_err(|_| Error::Disconnected)
+}
+
+// Frames in 'new' that weren't in 'old', in time order, and the paths of those that have gone
+fn changes(old: &FrameIndex, new: &FrameIndex, site: &RadarSite) -> (Vec<Frame>, Vec<String>) {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+
+    for lc_code in site.codes() {
+        for frame in new.frames(lc_code) {
+            if !old.contains(lc_code, frame.time) {
+                added.push(frame.clone());
+            }
+        }
+        for frame in old.frames(lc_code) {
+            if !new.contains(lc_code, frame.time) {
+                removed.push(frame.path.clone());
+            }
+        }
+    }
+
+    (added, removed)
+}
+
+// When the background and location layers of each range were last written, so replacements
+// can be noticed. Layers that can't be read are left out
+fn background_times(
+    site: &RadarSite,
+    config: &Config,
+) -> HashMap<String, (SystemTime, SystemTime)> {
+    let modified = |name: String| {
+        fs::metadata(config.layer_path(&name))
+            .and_then(|file| file.modified())
+            .ok()
+    };
+
+    site.codes()
+        .into_iter()
+        .filter_map(|lc_code| {
+            let background = modified(lc_code.to_string() + ".background.png")?;
+            let locations = modified(lc_code.to_string() + ".locations.png")?;
+            Some((lc_code.to_string(), (background, locations)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::mock::png;
+    use std::fs::File;
+    use temp_dir::TempDir;
+
+    #[test]
+    fn new_and_removed_frames_are_found() {
+        let temp = TempDir::new("watcher");
+        let config = Config {
+            download_dir: temp.path().to_string(),
+            ..Config::default()
+        };
+        let site = RadarSite::new("04", "Newcastle", &[3, 4]);
+
+        // A range without a folder yet has no frames
+        fs::create_dir_all(config.image_dir("IDR043")).unwrap();
+        let old_path = config.image_dir("IDR043") + "IDR043.T.201901010000.png";
+        fs::write(&old_path, png()).unwrap();
+        let old = FrameIndex::scan(&site, &config).unwrap();
+
+        // Another process removes a frame and adds some, one still being written
+        fs::remove_file(&old_path).unwrap();
+        fs::create_dir_all(config.image_dir("IDR044")).unwrap();
+        for path in &[
+            config.image_dir("IDR043") + "IDR043.T.201901010010.png",
+            config.image_dir("IDR044") + "IDR044.T.201901010010.png",
+        ] {
+            fs::write(path, png()).unwrap();
+        }
+        File::create(config.image_dir("IDR043") + ".IDR043.T.201901010020.png.part").unwrap();
+        let new = FrameIndex::scan(&site, &config).unwrap();
+
+        let (added, removed) = changes(&old, &new, &site);
+        let added: Vec<_> = added.iter().map(|frame| frame.path.clone()).collect();
+        assert_eq!(
+            added,
+            vec![
+                config.image_dir("IDR043") + "IDR043.T.201901010010.png",
+                config.image_dir("IDR044") + "IDR044.T.201901010010.png",
+            ]
+        );
+        assert_eq!(removed, vec![old_path]);
+
+        let (added, removed) = changes(&new, &new, &site);
+        assert!(added.is_empty() && removed.is_empty());
+
+        // Layers are looked for alongside the frames, and only count once both are there
+        for name in &[
+            "IDR043.background.png",
+            "IDR043.locations.png",
+            "IDR044.background.png",
+        ] {
+            fs::write(config.layer_path(name), png()).unwrap();
+        }
+        let backgrounds = background_times(&site, &config);
+        assert_eq!(backgrounds.keys().collect::<Vec<_>>(), vec!["IDR043"]);
+    }
+}